@@ -464,6 +464,8 @@ pub enum StatusCode {
     BAD_CHAIN_ID = 23,
     // The sequence number is too large and would overflow if the transaction were executed
     SEQUENCE_NUMBER_TOO_BIG = 24,
+    // Size of the published module exceeds the limit set in the VM config.
+    EXCEEDED_MAX_MODULE_SIZE = 25,
 
     // When a code module/script is published it is verified. These are the
     // possible errors that can arise from the verification process.
@@ -555,6 +557,12 @@ pub enum StatusCode {
     // The sender is trying to publish a module named `M`, but the sender's account already
     // contains a module with this name.
     DUPLICATE_MODULE_NAME = 1095,
+    // Republished module is not backward compatible with the published one.
+    BACKWARD_INCOMPATIBLE_MODULE_UPDATE = 1096,
+    // Code contains an instruction forbidden by the host opcode policy.
+    FORBIDDEN_OPCODE = 1097,
+    // Module can not be removed while published modules or resources depend on it.
+    MODULE_IN_USE = 1098,
 
     // These are errors that the VM might raise if a violation of internal
     // invariants takes place.
@@ -617,6 +625,10 @@ pub enum StatusCode {
     CALL_STACK_OVERFLOW = 4021,
     VM_MAX_TYPE_DEPTH_REACHED = 4024,
     VM_MAX_VALUE_DEPTH_REACHED = 4025,
+    // The transaction tried to write a resource under an address rejected by the host policy.
+    WRITE_TO_FORBIDDEN_ADDRESS = 4026,
+    // The host rejected a native balance operation.
+    NATIVE_BALANCE_OPERATION_FAILED = 4027,
 
     // A reserved status to represent an unknown vm status.
     // this is std::u64::MAX, but we can't pattern match on that, so put the hardcoded value in
@@ -4,7 +4,9 @@
 use crate::location::Loc;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt::Write;
 use hashbrown::HashMap;
+use serde::Serialize;
 
 //**************************************************************************************************
 // Types
@@ -16,3 +18,177 @@ pub type ErrorSlice = [(Loc, String)];
 pub type HashableError = Vec<(&'static str, usize, usize, String)>;
 
 pub type FilesSourceText = HashMap<&'static str, String>;
+
+/// Machine-readable label of an error, e.g. for editor integrations.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorLabel {
+    pub file: &'static str,
+    /// Byte offset of the span start.
+    pub start: usize,
+    /// Byte offset of the span end.
+    pub end: usize,
+    pub message: String,
+}
+
+impl From<&(Loc, String)> for ErrorLabel {
+    fn from((loc, message): &(Loc, String)) -> ErrorLabel {
+        ErrorLabel {
+            file: loc.file(),
+            start: loc.span().start() as usize,
+            end: loc.span().end() as usize,
+            message: message.clone(),
+        }
+    }
+}
+
+/// Converts errors into serializable labels, one list of labels per error.
+pub fn error_labels(errors: &Errors) -> Vec<Vec<ErrorLabel>> {
+    errors
+        .iter()
+        .map(|error| error.iter().map(ErrorLabel::from).collect())
+        .collect()
+}
+
+/// Converts an error into its hashable form.
+pub fn hashable_error(error: &ErrorSlice) -> HashableError {
+    error
+        .iter()
+        .map(|(loc, msg)| {
+            (
+                loc.file(),
+                loc.span().start() as usize,
+                loc.span().end() as usize,
+                msg.clone(),
+            )
+        })
+        .collect()
+}
+
+//**************************************************************************************************
+// Rendering
+//**************************************************************************************************
+
+/// Renders errors as human-readable diagnostics with source snippets.
+///
+/// The first label of an error is its primary message. Every label is printed with the source line
+/// it points to and carets under the spanned columns.
+pub fn render_errors(files: &FilesSourceText, errors: &Errors) -> String {
+    let mut out = String::new();
+    for (idx, error) in errors.iter().enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+        render_error(&mut out, files, error);
+    }
+    out
+}
+
+fn render_error(out: &mut String, files: &FilesSourceText, error: &ErrorSlice) {
+    let primary = match error.first() {
+        Some((_, msg)) => msg.as_str(),
+        None => return,
+    };
+    let labels = error
+        .iter()
+        .map(|(loc, msg)| (Label::new(files, *loc), msg))
+        .collect::<Vec<_>>();
+    let gutter = labels
+        .iter()
+        .filter_map(|(label, _)| label.as_ref().map(|l| digits(l.line)))
+        .max()
+        .unwrap_or(1);
+    let pad = " ".repeat(gutter);
+
+    let _ = writeln!(out, "error: {}", primary);
+    for ((label, msg), (loc, _)) in labels.iter().zip(error) {
+        match label {
+            Some(label) => {
+                let _ = writeln!(
+                    out,
+                    "{}--> {}:{}:{}",
+                    pad,
+                    loc.file(),
+                    label.line,
+                    label.column
+                );
+                let _ = writeln!(out, "{} |", pad);
+                let _ = writeln!(
+                    out,
+                    "{:>width$} | {}",
+                    label.line,
+                    label.text,
+                    width = gutter
+                );
+                let _ = writeln!(
+                    out,
+                    "{} | {}{} {}",
+                    pad,
+                    " ".repeat(label.column - 1),
+                    "^".repeat(label.width),
+                    msg
+                );
+            }
+            None => {
+                let span = loc.span();
+                let _ = writeln!(
+                    out,
+                    "{}--> {}:{}-{}: {}",
+                    pad,
+                    loc.file(),
+                    span.start(),
+                    span.end(),
+                    msg
+                );
+            }
+        }
+    }
+}
+
+/// Position of a label in its source file.
+struct Label<'a> {
+    /// One-based line number.
+    line: usize,
+    /// One-based column of the span start, in characters.
+    column: usize,
+    /// Number of spanned characters on the first line, at least one.
+    width: usize,
+    /// Source line the span starts on.
+    text: &'a str,
+}
+
+impl<'a> Label<'a> {
+    fn new(files: &'a FilesSourceText, loc: Loc) -> Option<Label<'a>> {
+        let source = files.get(loc.file())?;
+        let start = loc.span().start() as usize;
+        let end = loc.span().end() as usize;
+        if start > source.len() || !source.is_char_boundary(start) {
+            return None;
+        }
+
+        let line_start = source[..start].rfind('\n').map_or(0, |pos| pos + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |pos| start + pos);
+        let underline_end = end.min(line_end).max(start);
+        let text = &source[line_start..line_end];
+        let width = source
+            .get(start..underline_end)
+            .map_or(0, |spanned| spanned.chars().count());
+
+        Some(Label {
+            line: source[..line_start].matches('\n').count() + 1,
+            column: source[line_start..start].chars().count() + 1,
+            width: width.max(1),
+            text: text.trim_end_matches('\r'),
+        })
+    }
+}
+
+fn digits(mut num: usize) -> usize {
+    let mut digits = 1;
+    while num >= 10 {
+        num /= 10;
+        digits += 1;
+    }
+    digits
+}
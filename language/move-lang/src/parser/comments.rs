@@ -0,0 +1,89 @@
+// Copyright (c) The Diem Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::location::Loc;
+use crate::parser::ast::*;
+use crate::MatchedFileCommentMap;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+/// Identifies a top level declaration of a program.
+///
+/// Members of a script are identified with `None` as the module name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeclId {
+    Module(String),
+    Function(Option<String>, String),
+    Struct(String, String),
+    Constant(Option<String>, String),
+}
+
+/// Maps the declarations of the program to their documentation comments.
+///
+/// The parser keys matched doc comments by the start offset of the item which follows them, so a
+/// comment is attached to the declaration starting right after it. `comments` must be the map
+/// produced while parsing the file `program` was parsed from. Declarations without a
+/// documentation comment are not included.
+pub fn attach_doc_comments(
+    program: &Program,
+    comments: &MatchedFileCommentMap,
+) -> BTreeMap<DeclId, String> {
+    let mut docs = BTreeMap::new();
+    let mut attach = |loc: Loc, id: DeclId| {
+        if let Some(doc) = comments.get(&loc.span().start()) {
+            if !doc.is_empty() {
+                docs.insert(id, doc.clone());
+            }
+        }
+    };
+
+    let definitions = program
+        .source_definitions
+        .iter()
+        .chain(program.lib_definitions.iter());
+    for definition in definitions {
+        match definition {
+            Definition::Module(module) => attach_module(module, &mut attach),
+            Definition::Address(_, _, modules) => {
+                for module in modules {
+                    attach_module(module, &mut attach);
+                }
+            }
+            Definition::Script(script) => {
+                for constant in &script.constants {
+                    attach(
+                        constant.loc,
+                        DeclId::Constant(None, constant.name.0.value.clone()),
+                    );
+                }
+                attach(
+                    script.function.loc,
+                    DeclId::Function(None, script.function.name.0.value.clone()),
+                );
+            }
+        }
+    }
+    docs
+}
+
+fn attach_module<F: FnMut(Loc, DeclId)>(module: &ModuleDefinition, attach: &mut F) {
+    let module_name = &module.name.0.value;
+    attach(module.loc, DeclId::Module(module_name.clone()));
+    for member in &module.members {
+        match member {
+            ModuleMember::Function(function) => attach(
+                function.loc,
+                DeclId::Function(Some(module_name.clone()), function.name.0.value.clone()),
+            ),
+            ModuleMember::Struct(definition) => attach(
+                definition.loc,
+                DeclId::Struct(module_name.clone(), definition.name.0.value.clone()),
+            ),
+            ModuleMember::Constant(constant) => attach(
+                constant.loc,
+                DeclId::Constant(Some(module_name.clone()), constant.name.0.value.clone()),
+            ),
+            ModuleMember::Spec(_) | ModuleMember::Use(_) => {}
+        }
+    }
+}
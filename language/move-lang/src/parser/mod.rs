@@ -2,5 +2,6 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod ast;
+pub mod comments;
 pub mod lexer;
 pub mod syntax;
@@ -2291,3 +2291,50 @@ pub fn parse_file_string(
         }
     }
 }
+
+/// Parse the `input` string as a single Move expression, e.g. for a REPL. The whole input must
+/// form the expression, trailing tokens are reported as an error. The `file` name is used to
+/// identify source locations in error messages.
+pub fn parse_expression_string(file: &'static str, input: &str) -> Result<Exp, Errors> {
+    let mut tokens = Lexer::new(input, file, BTreeMap::new());
+    tokens.advance().map_err(|err| vec![err])?;
+    let exp = parse_exp(&mut tokens).map_err(|err| vec![err])?;
+    if tokens.peek() != Tok::EOF {
+        return Err(vec![unexpected_token_error(&tokens, "end-of-file")]);
+    }
+    Ok(exp)
+}
+
+/// Same as `parse_file_string`, but first rejects any character which is not printable ASCII,
+/// a tab or a line ending. The error points at the first offending character.
+pub fn parse_file_string_checked(
+    file: &'static str,
+    input: &str,
+    comment_map: BTreeMap<Span, String>,
+) -> Result<(Vec<Definition>, BTreeMap<u32, String>), Errors> {
+    verify_string(file, input)?;
+    parse_file_string(file, input, comment_map)
+}
+
+/// Checks that the input contains only permitted characters.
+pub fn verify_string(file: &'static str, input: &str) -> Result<(), Errors> {
+    match input
+        .char_indices()
+        .find(|(_, chr)| !is_permitted_char(*chr))
+    {
+        None => Ok(()),
+        Some((idx, chr)) => {
+            let loc = Loc::new(file, Span::new(idx as u32, idx as u32));
+            let msg = format!(
+                "Invalid character '{}' found when reading file. Only ASCII printable \
+                 characters, tabs (\\t), and line endings (\\n, \\r) are permitted.",
+                chr.escape_default()
+            );
+            Err(vec![vec![(loc, msg)]])
+        }
+    }
+}
+
+fn is_permitted_char(chr: char) -> bool {
+    matches!(chr, ' '..='~' | '\t' | '\n' | '\r')
+}
@@ -0,0 +1,39 @@
+use std::collections::BTreeMap;
+
+use move_lang::codespan::Span;
+use move_lang::parser::ast::Program;
+use move_lang::parser::comments::{attach_doc_comments, DeclId};
+use move_lang::parser::syntax::parse_file_string;
+
+const FILE: &str = "test.move";
+
+#[test]
+fn test_attach_doc_comment_to_function() {
+    // Comments are stripped from the source before parsing, their place is left blank.
+    let comment = "/// Doc for f.";
+    let source = format!(
+        "module M {{\n    {}\n    fun f() {{}}\n    fun g() {{}}\n}}\n",
+        " ".repeat(comment.len())
+    );
+    let start = "module M {\n    ".len() as u32;
+    let mut comment_map = BTreeMap::new();
+    comment_map.insert(
+        Span::new(start, start + comment.len() as u32),
+        " Doc for f.".to_owned(),
+    );
+
+    let (source_definitions, comments) = parse_file_string(FILE, &source, comment_map).unwrap();
+    let program = Program {
+        source_definitions,
+        lib_definitions: vec![],
+    };
+
+    let docs = attach_doc_comments(&program, &comments);
+    assert_eq!(docs.len(), 1);
+    assert_eq!(
+        docs.get(&DeclId::Function(Some("M".to_owned()), "f".to_owned())),
+        Some(&" Doc for f.".to_owned())
+    );
+    assert!(!docs.contains_key(&DeclId::Function(Some("M".to_owned()), "g".to_owned())));
+    assert!(!docs.contains_key(&DeclId::Module("M".to_owned())));
+}
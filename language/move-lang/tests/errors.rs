@@ -0,0 +1,109 @@
+use move_lang::codespan::Span;
+use move_lang::errors::{error_labels, hashable_error, render_errors, FilesSourceText};
+use move_lang::location::Loc;
+use serde_json::json;
+
+const FILE: &str = "test.move";
+const SOURCE: &str = "module M {\n    fun f() { x }\n}\n";
+
+fn loc(pattern: &str) -> Loc {
+    let start = SOURCE.find(pattern).unwrap() as u32;
+    Loc::new(FILE, Span::new(start, start + pattern.len() as u32))
+}
+
+fn files() -> FilesSourceText {
+    let mut files = FilesSourceText::new();
+    files.insert(FILE, SOURCE.to_owned());
+    files
+}
+
+#[test]
+fn test_render_single_label() {
+    let errors = vec![vec![(loc("x"), "Unbound variable 'x'".to_owned())]];
+    let rendered = render_errors(&files(), &errors);
+
+    assert_eq!(
+        rendered,
+        "error: Unbound variable 'x'\n \
+         --> test.move:2:15\n  \
+         |\n\
+         2 |     fun f() { x }\n  \
+         |               ^ Unbound variable 'x'\n"
+    );
+}
+
+#[test]
+fn test_render_multiple_labels() {
+    let errors = vec![
+        vec![
+            (loc("x"), "Unbound variable 'x'".to_owned()),
+            (loc("fun f"), "In this function".to_owned()),
+        ],
+        vec![(loc("module M"), "Invalid module".to_owned())],
+    ];
+    let rendered = render_errors(&files(), &errors);
+
+    assert!(rendered.contains("test.move:2:15"));
+    assert!(rendered.contains("test.move:2:5"));
+    assert!(rendered.contains("  |     ^^^^^ In this function\n"));
+    assert!(rendered.contains("\nerror: Invalid module\n"));
+    assert!(rendered.contains("test.move:1:1"));
+    assert!(rendered.contains("1 | module M {\n"));
+    assert!(rendered.contains("  | ^^^^^^^^ Invalid module\n"));
+}
+
+#[test]
+fn test_render_unknown_file() {
+    let errors = vec![vec![(
+        Loc::new("missing.move", Span::new(3, 5)),
+        "Unknown".to_owned(),
+    )]];
+    let rendered = render_errors(&files(), &errors);
+
+    assert_eq!(rendered, "error: Unknown\n --> missing.move:3-5: Unknown\n");
+}
+
+#[test]
+fn test_error_labels_json() {
+    let x = loc("x");
+    let f = loc("fun f");
+    let errors = vec![vec![
+        (x, "Unbound variable 'x'".to_owned()),
+        (f, "In this function".to_owned()),
+    ]];
+
+    assert_eq!(
+        serde_json::to_value(error_labels(&errors)).unwrap(),
+        json!([[
+            {
+                "file": FILE,
+                "start": x.span().start(),
+                "end": x.span().end(),
+                "message": "Unbound variable 'x'",
+            },
+            {
+                "file": FILE,
+                "start": f.span().start(),
+                "end": f.span().end(),
+                "message": "In this function",
+            },
+        ]])
+    );
+    assert_eq!(
+        hashable_error(&errors[0]),
+        vec![
+            (
+                FILE,
+                x.span().start() as usize,
+                x.span().end() as usize,
+                "Unbound variable 'x'".to_owned()
+            ),
+            (
+                FILE,
+                f.span().start() as usize,
+                f.span().end() as usize,
+                "In this function".to_owned()
+            ),
+        ]
+    );
+}
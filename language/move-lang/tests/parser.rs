@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+
+use move_lang::codespan::Span;
+use move_lang::location::Loc;
+use move_lang::parser::ast::{BinOp_, Exp_};
+use move_lang::parser::syntax::{parse_expression_string, parse_file_string_checked};
+
+const FILE: &str = "test.move";
+
+#[test]
+fn test_checked_parse_accepts_ascii() {
+    let source = "module M {\n\tfun f() {}\r\n}\n";
+    let (defs, _) = parse_file_string_checked(FILE, source, BTreeMap::new()).unwrap();
+    assert_eq!(defs.len(), 1);
+}
+
+#[test]
+fn test_checked_parse_rejects_nul() {
+    let source = "module M {\n    fun f() {}\0\n}\n";
+    let errors = parse_file_string_checked(FILE, source, BTreeMap::new()).unwrap_err();
+
+    let offset = source.find('\0').unwrap() as u32;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].len(), 1);
+    let (loc, msg) = &errors[0][0];
+    assert_eq!(loc, &Loc::new(FILE, Span::new(offset, offset)));
+    assert!(msg.starts_with("Invalid character '\\u{0}'"));
+}
+
+#[test]
+fn test_checked_parse_rejects_non_ascii() {
+    let source = "module M {\n    fun é() {}\n}\n";
+    let errors = parse_file_string_checked(FILE, source, BTreeMap::new()).unwrap_err();
+    let offset = source.find('é').unwrap() as u32;
+    assert_eq!(errors[0][0].0, Loc::new(FILE, Span::new(offset, offset)));
+}
+
+#[test]
+fn test_parse_expression_binop() {
+    let exp = parse_expression_string(FILE, "1 + 2").unwrap();
+    match exp.value {
+        Exp_::BinopExp(left, op, right) => {
+            assert_eq!(left.value, Exp_::InferredNum(1));
+            assert_eq!(op.value, BinOp_::Add);
+            assert_eq!(right.value, Exp_::InferredNum(2));
+        }
+        exp => panic!("Unexpected expression: {:?}", exp),
+    }
+    assert_eq!(exp.loc, Loc::new(FILE, Span::new(0, 5)));
+}
+
+#[test]
+fn test_parse_expression_call() {
+    let exp = parse_expression_string(FILE, "0x1::Signer::address_of(&account)").unwrap();
+    match exp.value {
+        Exp_::Call(_, _, args) => assert_eq!(args.value.len(), 1),
+        exp => panic!("Unexpected expression: {:?}", exp),
+    }
+}
+
+#[test]
+fn test_parse_expression_errors() {
+    let errors = parse_expression_string(FILE, "1 + ").unwrap_err();
+    assert_eq!(errors[0][0].0, Loc::new(FILE, Span::new(4, 4)));
+    assert_eq!(errors[0][0].1, "Unexpected end-of-file");
+
+    let errors = parse_expression_string(FILE, "1 + 2 3").unwrap_err();
+    assert_eq!(errors[0][0].0, Loc::new(FILE, Span::new(6, 7)));
+    assert_eq!(errors[0][1].1, "Expected end-of-file");
+}
@@ -60,7 +60,7 @@ pub fn native_deposit(
 
     let wallet_id = wallet_id(context, address, ty_args.pop().unwrap())?;
 
-    if let Some(balance) = context.get_balance(&wallet_id) {
+    if let Some(balance) = context.get_balance(&wallet_id)? {
         if balance >= amount {
             context.save_balance_operation(wallet_id, BalanceOperation::Deposit(amount));
             let cost = native_gas(context.cost_table(), NativeCostIndex::DEPOSIT, 0);
@@ -110,7 +110,7 @@ pub fn get_balance(
     let address = account_address(&pop_arg!(arguments, SignerRef).borrow_signer()?.0)?;
     let wallet_id = wallet_id(context, address, ty_args.pop().unwrap())?;
 
-    if let Some(balance) = context.get_balance(&wallet_id) {
+    if let Some(balance) = context.get_balance(&wallet_id)? {
         let cost = native_gas(context.cost_table(), NativeCostIndex::GET_BALANCE, 0);
         Ok(NativeResult::ok(
             cost,
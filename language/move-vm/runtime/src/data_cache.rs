@@ -124,6 +124,15 @@ impl<'r, 'l, R: RemoteCache, B: NativeBalance> TransactionDataCache<'r, 'l, R, B
         }
     }
 
+    /// Reads the resource from the remote cache, ignoring the changes of this transaction.
+    pub(crate) fn get_resource_blob(
+        &self,
+        addr: &AccountAddress,
+        tag: &StructTag,
+    ) -> PartialVMResult<Option<Vec<u8>>> {
+        self.remote.get_resource(addr, tag)
+    }
+
     /// Make a write set from the updated (dirty, deleted) global resources along with
     /// published modules.
     ///
@@ -179,6 +188,10 @@ impl<'r, 'l, R: RemoteCache, B: NativeBalance> TransactionDataCache<'r, 'l, R, B
         })
     }
 
+    pub(crate) fn num_wallet_operations(&self) -> u64 {
+        self.master_of_coin.num_operations() as u64
+    }
+
     pub(crate) fn num_mutated_accounts(&self, sender: &AccountAddress) -> u64 {
         // The sender's account will always be mutated.
         let mut total_mutated_accounts: u64 = 1;
@@ -280,7 +293,7 @@ impl<'r, 'l, C: RemoteCache, B: NativeBalance> DataStore for TransactionDataCach
             Ok(Some(bytes)) => Ok(bytes),
             Ok(None) => Err(PartialVMError::new(StatusCode::LINKER_ERROR)
                 .with_message(format!("Cannot find {:?} in data cache", module_id))
-                .finish(Location::Undefined)),
+                .finish(Location::Module(module_id.clone()))),
             Err(err) => {
                 let msg = format!("Unexpected storage error: {:?}", err);
                 let (_old_status, _old_sub_status, _old_message, location, indices, offsets) =
@@ -328,7 +341,7 @@ impl<'r, 'l, C: RemoteCache, B: NativeBalance> DataStore for TransactionDataCach
         Ok(self.event_data.push((address, ty, ty_layout, val, caller)))
     }
 
-    fn get_balance(&self, wallet_id: &WalletId) -> Option<u128> {
+    fn get_balance(&self, wallet_id: &WalletId) -> PartialVMResult<Option<u128>> {
         self.master_of_coin.get_balance(wallet_id)
     }
 
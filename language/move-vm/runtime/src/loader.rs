@@ -15,7 +15,7 @@ use bytecode_verifier::{
 use core::cell::RefCell;
 use core::{fmt::Debug, hash::Hash};
 use diem_crypto::HashValue;
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use mirai_annotations::assume;
 use move_core_types::{
     identifier::{IdentStr, Identifier},
@@ -25,7 +25,7 @@ use move_core_types::{
 };
 use move_vm_types::{
     data_store::DataStore,
-    loaded_data::runtime_types::{StructType, Type},
+    loaded_data::runtime_types::{StructType, Type, TYPE_DEPTH_MAX},
 };
 use vm::{
     access::{ModuleAccess, ScriptAccess},
@@ -72,6 +72,12 @@ where
             .get(&key)
             .and_then(|idx| self.binaries.get(*idx))
     }
+
+    // Makes the binary unreachable by key. The binary itself stays in the vector, as its
+    // position may still be referenced.
+    fn remove(&mut self, key: &K) -> bool {
+        self.id_map.remove(key).is_some()
+    }
 }
 
 // A script cache is a map from the hash value of a script and the `Script` itself.
@@ -119,6 +125,28 @@ impl ModuleCache {
         }
     }
 
+    // Evicts the module and all loaded modules depending on it, directly or transitively.
+    // Types and functions of evicted modules stay in the global tables, so indices held by
+    // other modules remain valid; evicted modules are loaded again on the next access.
+    fn remove(&mut self, id: &ModuleId) {
+        let mut pending = vec![id.clone()];
+        while let Some(id) = pending.pop() {
+            if !self.modules.remove(&id) {
+                continue;
+            }
+            for (dependent, idx) in &self.modules.id_map {
+                let module = self.modules.binaries[*idx].module();
+                let depends = module
+                    .module_handles()
+                    .iter()
+                    .any(|handle| module.module_id_for_handle(handle) == id);
+                if depends {
+                    pending.push(dependent.clone());
+                }
+            }
+        }
+    }
+
     //
     // Common "get" operations
     //
@@ -416,24 +444,67 @@ pub(crate) struct Loader {
     scripts: RefCell<ScriptCache>,
     module_cache: RefCell<ModuleCache>,
     type_cache: RefCell<TypeCache>,
+    pinned: RefCell<HashSet<ModuleId>>,
+    max_type_depth: usize,
 }
 
 impl Loader {
     pub(crate) fn new() -> Self {
+        Self::with_max_type_depth(TYPE_DEPTH_MAX)
+    }
+
+    /// Creates a loader which rejects type arguments nested deeper than `max_type_depth`.
+    pub(crate) fn with_max_type_depth(max_type_depth: usize) -> Self {
         Self {
             scripts: RefCell::new(ScriptCache::new()),
             module_cache: RefCell::new(ModuleCache::new()),
             type_cache: RefCell::new(TypeCache::new()),
+            pinned: RefCell::new(HashSet::new()),
+            max_type_depth,
         }
     }
 
     /// Clears loader cache.
+    /// Pinned modules stay pinned and are loaded again by `load_pinned`.
     pub(crate) fn clear(&self) {
         *self.scripts.borrow_mut() = ScriptCache::new();
         *self.module_cache.borrow_mut() = ModuleCache::new();
         *self.type_cache.borrow_mut() = TypeCache::new();
     }
 
+    /// Evicts the module and its dependents from the loader cache.
+    /// Scripts are evicted as well, since they may call into the evicted modules.
+    pub(crate) fn clear_module(&self, id: &ModuleId) {
+        self.module_cache.borrow_mut().remove(id);
+        *self.scripts.borrow_mut() = ScriptCache::new();
+    }
+
+    /// Loads the pinned modules with their dependencies into the cache, e.g. after `clear`.
+    pub(crate) fn load_pinned(
+        &self,
+        data_store: &mut impl DataStore,
+        log_context: &impl LogContext,
+    ) -> VMResult<()> {
+        let mut pinned = self.pinned.borrow().iter().cloned().collect::<Vec<_>>();
+        pinned.sort();
+        for id in pinned {
+            self.load_module_verify_no_missing_dependencies(&id, data_store, log_context)?;
+        }
+        Ok(())
+    }
+
+    /// Loads the module with its dependencies and marks it to be loaded again by `load_pinned`.
+    pub(crate) fn pin_module(
+        &self,
+        id: &ModuleId,
+        data_store: &mut impl DataStore,
+        log_context: &impl LogContext,
+    ) -> VMResult<()> {
+        self.load_module_verify_no_missing_dependencies(id, data_store, log_context)?;
+        self.pinned.borrow_mut().insert(id.clone());
+        Ok(())
+    }
+
     //
     // Script verification and loading
     //
@@ -593,6 +664,21 @@ impl Loader {
         self.verify_module(module, data_store, true, log_context)
     }
 
+    /// Links the module against its dependencies without running the bytecode verifier.
+    ///
+    /// Only for trusted modules, which are known to pass verification.
+    pub(crate) fn link_module_verify_no_missing_dependencies(
+        &self,
+        module: &CompiledModule,
+        data_store: &mut impl DataStore,
+        log_context: &impl LogContext,
+    ) -> VMResult<()> {
+        let deps = module_dependencies(module);
+        let loaded_deps =
+            self.load_dependencies_verify_no_missing_dependencies(deps, data_store, log_context)?;
+        self.verify_module_dependencies(module, loaded_deps)
+    }
+
     fn verify_module_expect_no_missing_dependencies(
         &self,
         module: &CompiledModule,
@@ -691,6 +777,24 @@ impl Loader {
         data_store: &mut impl DataStore,
         log_context: &impl LogContext,
     ) -> VMResult<Type> {
+        self.load_type_at_depth(type_tag, 0, data_store, log_context)
+    }
+
+    fn load_type_at_depth(
+        &self,
+        type_tag: &TypeTag,
+        depth: usize,
+        data_store: &mut impl DataStore,
+        log_context: &impl LogContext,
+    ) -> VMResult<Type> {
+        if depth > self.max_type_depth {
+            return Err(PartialVMError::new(StatusCode::VM_MAX_TYPE_DEPTH_REACHED)
+                .with_message(format!(
+                    "Type {} is nested deeper than {}",
+                    type_tag, self.max_type_depth
+                ))
+                .finish(Location::Undefined));
+        }
         Ok(match type_tag {
             TypeTag::Bool => Type::Bool,
             TypeTag::U8 => Type::U8,
@@ -698,9 +802,12 @@ impl Loader {
             TypeTag::U128 => Type::U128,
             TypeTag::Address => Type::Address,
             TypeTag::Signer => Type::Signer,
-            TypeTag::Vector(tt) => {
-                Type::Vector(Box::new(self.load_type(tt, data_store, log_context)?))
-            }
+            TypeTag::Vector(tt) => Type::Vector(Box::new(self.load_type_at_depth(
+                tt,
+                depth + 1,
+                data_store,
+                log_context,
+            )?)),
             TypeTag::Struct(struct_tag) => {
                 let module_id = ModuleId::new(struct_tag.address, struct_tag.module.clone());
                 self.load_module_verify_no_missing_dependencies(
@@ -719,7 +826,12 @@ impl Loader {
                 } else {
                     let mut type_params = vec![];
                     for ty_param in &struct_tag.type_params {
-                        type_params.push(self.load_type(ty_param, data_store, log_context)?);
+                        type_params.push(self.load_type_at_depth(
+                            ty_param,
+                            depth + 1,
+                            data_store,
+                            log_context,
+                        )?);
                     }
                     self.verify_ty_args(&struct_type.type_parameters, &type_params)
                         .map_err(|e| e.finish(Location::Undefined))?;
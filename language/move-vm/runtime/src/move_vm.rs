@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{data_cache::RemoteCache, runtime::VMRuntime, session::Session};
+use move_core_types::language_storage::ModuleId;
 use move_vm_types::natives::balance::NativeBalance;
 
 pub struct MoveVM {
@@ -15,6 +16,13 @@ impl MoveVM {
         }
     }
 
+    /// Creates a vm which rejects type arguments nested deeper than `max_type_depth`.
+    pub fn with_max_type_depth(max_type_depth: usize) -> Self {
+        Self {
+            runtime: VMRuntime::with_max_type_depth(max_type_depth),
+        }
+    }
+
     /// Create a new Session backed by the given storage.
     ///
     /// Right now it is the caller's responsibility to ensure cache coherence of the Move VM Loader
@@ -38,9 +46,17 @@ impl MoveVM {
     }
 
     /// Clears vm state.
+    ///
+    /// Modules pinned with `Session::pin_module` stay pinned, but must be loaded again with
+    /// `Session::load_pinned_modules`.
     pub fn clear(&self) {
         self.runtime.clear();
     }
+
+    /// Evicts the module and the modules depending on it from the loader cache.
+    pub fn clear_module(&self, id: &ModuleId) {
+        self.runtime.clear_module(id);
+    }
 }
 
 impl Default for MoveVM {
@@ -241,7 +241,7 @@ impl<'a, L: LogContext> NativeContext for FunctionContext<'a, L> {
         self.caller
     }
 
-    fn get_balance(&self, wallet_id: &WalletId) -> Option<Balance> {
+    fn get_balance(&self, wallet_id: &WalletId) -> PartialVMResult<Option<Balance>> {
         self.data_store.get_balance(wallet_id)
     }
 
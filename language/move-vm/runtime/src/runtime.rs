@@ -39,6 +39,12 @@ impl VMRuntime {
         }
     }
 
+    pub(crate) fn with_max_type_depth(max_type_depth: usize) -> Self {
+        VMRuntime {
+            loader: Loader::with_max_type_depth(max_type_depth),
+        }
+    }
+
     pub fn new_session<'r, R: RemoteCache, B: NativeBalance>(
         &self,
         remote: &'r R,
@@ -55,11 +61,34 @@ impl VMRuntime {
         self.loader.clear();
     }
 
-    // See Session::publish_module for what contracts to follow.
+    pub(crate) fn clear_module(&self, id: &ModuleId) {
+        self.loader.clear_module(id);
+    }
+
+    pub(crate) fn load_pinned(
+        &self,
+        data_store: &mut impl DataStore,
+        log_context: &impl LogContext,
+    ) -> VMResult<()> {
+        self.loader.load_pinned(data_store, log_context)
+    }
+
+    pub(crate) fn pin_module(
+        &self,
+        id: &ModuleId,
+        data_store: &mut impl DataStore,
+        log_context: &impl LogContext,
+    ) -> VMResult<()> {
+        self.loader.pin_module(id, data_store, log_context)
+    }
+
+    // See Session::publish_module and Session::republish_module for what contracts to follow.
     pub(crate) fn publish_module(
         &self,
         module: Vec<u8>,
         sender: AccountAddress,
+        republish: bool,
+        verify: bool,
         data_store: &mut impl DataStore,
         _cost_strategy: &mut CostStrategy,
         log_context: &impl LogContext,
@@ -86,20 +115,28 @@ impl VMRuntime {
         }
 
         // Make sure that there is not already a module with this name published
-        // under the transaction sender's account.
+        // under the transaction sender's account unless the module is being republished.
         let module_id = compiled_module.self_id();
-        if data_store.exists_module(&module_id)? {
+        if !republish && data_store.exists_module(&module_id)? {
             return Err(
                 PartialVMError::new(StatusCode::DUPLICATE_MODULE_NAME).finish(Location::Undefined)
             );
         };
 
         // perform bytecode and loading verification
-        self.loader.verify_module_verify_no_missing_dependencies(
-            &compiled_module,
-            data_store,
-            log_context,
-        )?;
+        if verify {
+            self.loader.verify_module_verify_no_missing_dependencies(
+                &compiled_module,
+                data_store,
+                log_context,
+            )?;
+        } else {
+            self.loader.link_module_verify_no_missing_dependencies(
+                &compiled_module,
+                data_store,
+                log_context,
+            )?;
+        }
 
         data_store.publish_module(&module_id, module)
     }
@@ -10,7 +10,7 @@ use alloc::vec::Vec;
 use move_core_types::{
     account_address::AccountAddress,
     identifier::IdentStr,
-    language_storage::{ModuleId, TypeTag},
+    language_storage::{ModuleId, StructTag, TypeTag},
 };
 use move_vm_types::natives::balance::NativeBalance;
 use move_vm_types::{gas_schedule::CostStrategy, values::Value};
@@ -112,16 +112,95 @@ impl<'r, 'l, R: RemoteCache, B: NativeBalance> Session<'r, 'l, R, B> {
         self.runtime.publish_module(
             module,
             sender,
+            false,
+            true,
             &mut self.data_cache,
             cost_strategy,
             log_context,
         )
     }
 
+    /// Publish the given module without running the bytecode verifier.
+    ///
+    /// The module is still deserialized and linked against its dependencies. Only for trusted
+    /// modules known to pass verification, e.g. the standard library at genesis.
+    #[cfg(feature = "trusted_publish")]
+    pub fn publish_module_unverified(
+        &mut self,
+        module: Vec<u8>,
+        sender: AccountAddress,
+        cost_strategy: &mut CostStrategy,
+        log_context: &impl LogContext,
+    ) -> VMResult<()> {
+        self.runtime.publish_module(
+            module,
+            sender,
+            false,
+            false,
+            &mut self.data_cache,
+            cost_strategy,
+            log_context,
+        )
+    }
+
+    /// Publish the given module replacing the one with the same ModuleId if it exists.
+    ///
+    /// Follows the same contract as `publish_module` except that an existing module is not an
+    /// error. It is the caller's responsibility to check that the new module is compatible with
+    /// the published one and to clear the loader cache once the effects are applied.
+    pub fn republish_module(
+        &mut self,
+        module: Vec<u8>,
+        sender: AccountAddress,
+        cost_strategy: &mut CostStrategy,
+        log_context: &impl LogContext,
+    ) -> VMResult<()> {
+        self.runtime.publish_module(
+            module,
+            sender,
+            true,
+            true,
+            &mut self.data_cache,
+            cost_strategy,
+            log_context,
+        )
+    }
+
+    /// Loads the published module with its dependencies into the loader cache and pins it, so
+    /// that `load_pinned_modules` loads it again after `MoveVM::clear`.
+    pub fn pin_module(&mut self, id: &ModuleId, log_context: &impl LogContext) -> VMResult<()> {
+        self.runtime
+            .pin_module(id, &mut self.data_cache, log_context)
+    }
+
+    /// Loads all pinned modules into the loader cache.
+    pub fn load_pinned_modules(&mut self, log_context: &impl LogContext) -> VMResult<()> {
+        self.runtime.load_pinned(&mut self.data_cache, log_context)
+    }
+
     pub fn num_mutated_accounts(&self, sender: &AccountAddress) -> u64 {
         self.data_cache.num_mutated_accounts(sender)
     }
 
+    /// Returns the number of balance operations made by the session.
+    pub fn num_wallet_operations(&self) -> u64 {
+        self.data_cache.num_wallet_operations()
+    }
+
+    /// Returns the resource blob stored before this session.
+    ///
+    /// Writes made by the session are not visible, which allows the caller to compare the
+    /// previous value with the one in the effects.
+    pub fn get_resource_blob(
+        &self,
+        addr: &AccountAddress,
+        tag: &StructTag,
+    ) -> VMResult<Option<Vec<u8>>> {
+        self.data_cache
+            .get_resource_blob(addr, tag)
+            .map_err(|e| e.finish(Location::Undefined))
+    }
+
     /// Finish up the session and produce the side effects.
     ///
     /// This function should always succeed with no user errors returned, barring invariant violations.
@@ -56,7 +56,7 @@ pub trait DataStore {
     // ---
 
     /// Returns the balance by balance id.
-    fn get_balance(&self, wallet_id: &WalletId) -> Option<Balance>;
+    fn get_balance(&self, wallet_id: &WalletId) -> PartialVMResult<Option<Balance>>;
 
     /// Save balance operation.
     fn save_balance_operation(&mut self, wallet_id: WalletId, balance_op: BalanceOperation);
@@ -4,6 +4,8 @@ use core::fmt::{Display, Formatter};
 use hashbrown::HashMap;
 use move_core_types::account_address::AccountAddress;
 use move_core_types::language_storage::StructTag;
+use move_core_types::vm_status::StatusCode;
+use vm::errors::{PartialVMError, PartialVMResult};
 
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct WalletId {
@@ -31,6 +33,9 @@ pub type Balance = u128;
 
 pub trait NativeBalance {
     fn get_balance(&self, address: &WalletId) -> Option<Balance>;
+
+    /// Called for every balance operation made by the transaction, in execution order.
+    fn on_balance_operation(&self, _wallet_id: &WalletId, _op: &BalanceOperation) {}
 }
 
 #[derive(Debug)]
@@ -75,6 +80,7 @@ impl BalanceOperation {
 pub struct MasterOfCoin<B: NativeBalance> {
     native_balances: B,
     bank: HashMap<WalletId, BalanceOperation>,
+    num_operations: usize,
 }
 
 impl<B> MasterOfCoin<B>
@@ -85,43 +91,58 @@ where
         MasterOfCoin {
             native_balances,
             bank: Default::default(),
+            num_operations: 0,
         }
     }
 
-    pub fn get_balance(&self, wallet_id: &WalletId) -> Option<Balance> {
-        self.native_balances
-            .get_balance(wallet_id)
-            .map(|mut balance| {
-                if let Some(op) = self.bank.get(wallet_id) {
-                    match op {
-                        BalanceOperation::Deposit(val) => {
-                            balance -= *val;
-                        }
-                        BalanceOperation::Withdraw(val) => {
-                            balance += *val;
-                        }
-                    }
-                }
-                balance
-            })
-            .or_else(|| {
-                self.bank.get(wallet_id).and_then(|op| {
-                    if let BalanceOperation::Withdraw(val) = op {
-                        Some(*val)
-                    } else {
-                        None
-                    }
-                })
-            })
+    /// Returns the number of balance operations made by the transaction.
+    ///
+    /// Every operation is counted, including repeated operations on the same wallet.
+    pub fn num_operations(&self) -> usize {
+        self.num_operations
+    }
+
+    /// Returns the native balance with the operations of the transaction applied.
+    ///
+    /// Fails with `NATIVE_BALANCE_OPERATION_FAILED` if the operations are inconsistent with the
+    /// native balance, e.g. more coins were deposited than the wallet holds.
+    pub fn get_balance(&self, wallet_id: &WalletId) -> PartialVMResult<Option<Balance>> {
+        let op = self.bank.get(wallet_id);
+        match (self.native_balances.get_balance(wallet_id), op) {
+            (Some(balance), None) => Ok(Some(balance)),
+            (Some(balance), Some(BalanceOperation::Deposit(val))) => balance
+                .checked_sub(*val)
+                .map(Some)
+                .ok_or_else(|| balance_operation_error(wallet_id, balance, op)),
+            (Some(balance), Some(BalanceOperation::Withdraw(val))) => balance
+                .checked_add(*val)
+                .map(Some)
+                .ok_or_else(|| balance_operation_error(wallet_id, balance, op)),
+            (None, Some(BalanceOperation::Withdraw(val))) => Ok(Some(*val)),
+            (None, _) => Ok(None),
+        }
     }
 
     pub fn save_balance_operation(&mut self, wallet_id: WalletId, op: BalanceOperation) {
+        self.native_balances.on_balance_operation(&wallet_id, &op);
+        self.num_operations += 1;
         let entry = self.bank.entry(wallet_id);
         let current_op = entry.or_insert_with(BalanceOperation::empty);
         current_op.merge(op);
     }
 }
 
+fn balance_operation_error(
+    wallet_id: &WalletId,
+    balance: Balance,
+    op: Option<&BalanceOperation>,
+) -> PartialVMError {
+    PartialVMError::new(StatusCode::NATIVE_BALANCE_OPERATION_FAILED).with_message(format!(
+        "Balance operation {:?} on {} is inconsistent with the native balance {}.",
+        op, wallet_id, balance
+    ))
+}
+
 impl<B: NativeBalance> From<MasterOfCoin<B>> for HashMap<WalletId, BalanceOperation> {
     fn from(moc: MasterOfCoin<B>) -> Self {
         moc.bank
@@ -59,7 +59,7 @@ pub trait NativeContext {
     /// Caller module.
     fn caller(&self) -> Option<&ModuleId>;
     /// Get user Balance.
-    fn get_balance(&self, wallet_id: &WalletId) -> Option<Balance>;
+    fn get_balance(&self, wallet_id: &WalletId) -> PartialVMResult<Option<Balance>>;
     /// Save balance operation.
     fn save_balance_operation(&mut self, wallet_id: WalletId, balance_op: BalanceOperation);
 }
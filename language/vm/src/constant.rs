@@ -44,6 +44,10 @@ impl Constant {
         })
     }
 
+    pub fn layout(&self) -> Option<MoveTypeLayout> {
+        sig_to_ty(&self.type_)
+    }
+
     pub fn deserialize_constant(&self) -> Option<MoveValue> {
         let ty = sig_to_ty(&self.type_)?;
         MoveValue::simple_deserialize(&self.data, &ty).ok()
@@ -37,11 +37,35 @@
 
 use alloc::string::String;
 use alloc::vec::Vec;
+use anyhow::{anyhow, Error};
+use core::convert::TryFrom;
 use core::fmt;
 use diem_crypto::hash::HashValue;
 use move_core_types::account_address::AccountAddress;
 use move_core_types::language_storage::{ModuleId, ResourceKey, StructTag, CODE_TAG, RESOURCE_TAG};
 
+/// Derives the path part of storage keys.
+pub trait AccessPathHasher {
+    /// Returns the path of the resource with the given type.
+    fn resource_path(&self, tag: &StructTag) -> Vec<u8>;
+    /// Returns the path of the module with the given id.
+    fn code_path(&self, id: &ModuleId) -> Vec<u8>;
+}
+
+/// Default key derivation: the tag byte followed by the bcs encoding of the type or module id.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultAccessPathHasher;
+
+impl AccessPathHasher for DefaultAccessPathHasher {
+    fn resource_path(&self, tag: &StructTag) -> Vec<u8> {
+        tag.access_vector()
+    }
+
+    fn code_path(&self, id: &ModuleId) -> Vec<u8> {
+        id.access_vector()
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct AccessPath {
     pub address: AccountAddress,
@@ -57,28 +81,81 @@ impl AccessPath {
     }
 
     pub fn resource_access_vec(tag: &StructTag) -> Vec<u8> {
-        tag.access_vector()
+        AccessPath::resource_access_vec_with(tag, &DefaultAccessPathHasher)
+    }
+
+    pub fn resource_access_vec_with(tag: &StructTag, hasher: &impl AccessPathHasher) -> Vec<u8> {
+        hasher.resource_path(tag)
     }
 
     /// Convert Accesses into a byte offset which would be used by the storage layer to resolve
     /// where fields are stored.
     pub fn resource_access_path(key: &ResourceKey) -> AccessPath {
-        let path = AccessPath::resource_access_vec(&key.type_());
+        AccessPath::resource_access_path_with(key, &DefaultAccessPathHasher)
+    }
+
+    /// Same as `resource_access_path`, with the path derived by `hasher`.
+    pub fn resource_access_path_with(
+        key: &ResourceKey,
+        hasher: &impl AccessPathHasher,
+    ) -> AccessPath {
         AccessPath {
             address: key.address(),
-            path,
+            path: AccessPath::resource_access_vec_with(&key.type_(), hasher),
         }
     }
 
-    fn code_access_path_vec(key: &ModuleId) -> Vec<u8> {
-        key.access_vector()
+    pub fn code_access_path(key: &ModuleId) -> AccessPath {
+        AccessPath::code_access_path_with(key, &DefaultAccessPathHasher)
     }
 
-    pub fn code_access_path(key: &ModuleId) -> AccessPath {
-        let path = AccessPath::code_access_path_vec(key);
+    /// Same as `code_access_path`, with the path derived by `hasher`.
+    pub fn code_access_path_with(key: &ModuleId, hasher: &impl AccessPathHasher) -> AccessPath {
         AccessPath {
             address: *key.address(),
-            path,
+            path: hasher.code_path(key),
+        }
+    }
+
+    /// Decodes a raw storage key back into an access path.
+    ///
+    /// Only keys derived with `DefaultAccessPathHasher` can be decoded.
+    ///
+    /// Resource keys are laid out as `address ++ RESOURCE_TAG ++ bcs(StructTag)`, module keys as
+    /// `CODE_TAG ++ bcs(ModuleId)`.
+    pub fn decode_key(key: &[u8]) -> Result<AccessPath, Error> {
+        if key.len() > AccountAddress::LENGTH && key[AccountAddress::LENGTH] == RESOURCE_TAG {
+            let path = &key[AccountAddress::LENGTH..];
+            if bcs::from_bytes::<StructTag>(&path[1..]).is_ok() {
+                return Ok(AccessPath {
+                    address: AccountAddress::try_from(&key[..AccountAddress::LENGTH])?,
+                    path: path.to_vec(),
+                });
+            }
+        }
+
+        if key.first() == Some(&CODE_TAG) {
+            if let Ok(id) = bcs::from_bytes::<ModuleId>(&key[1..]) {
+                return Ok(AccessPath::from(&id));
+            }
+        }
+
+        Err(anyhow!("Unknown storage key: {}", hex::encode(key)))
+    }
+
+    /// Returns the resource type if this is a resource path.
+    pub fn resource_tag(&self) -> Option<StructTag> {
+        match self.path.split_first() {
+            Some((&RESOURCE_TAG, tag)) => bcs::from_bytes(tag).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the module id if this is a module path.
+    pub fn module_id(&self) -> Option<ModuleId> {
+        match self.path.split_first() {
+            Some((&CODE_TAG, id)) => bcs::from_bytes(id).ok(),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,26 @@
+use vm::normalized::Module;
+
+/// Checks that `new` module can replace `old` one without breaking its dependents
+/// and the resources already stored on chain.
+///
+/// Every struct of the old module must be preserved with the same layout and every public
+/// function must keep its signature. New structs and functions can be added.
+pub fn is_compatible(old: &Module, new: &Module) -> bool {
+    if old.address != new.address || old.name != new.name {
+        return false;
+    }
+
+    let structs_preserved = old.structs.iter().all(|old_struct| {
+        new.structs
+            .iter()
+            .any(|new_struct| new_struct == old_struct)
+    });
+
+    let functions_preserved = old.public_functions.iter().all(|old_func| {
+        new.public_functions
+            .iter()
+            .any(|new_func| new_func == old_func)
+    });
+
+    structs_preserved && functions_preserved
+}
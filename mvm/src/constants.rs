@@ -0,0 +1,26 @@
+use alloc::vec::Vec;
+
+use move_core_types::value::MoveTypeLayout;
+use move_core_types::vm_status::StatusCode;
+use vm::access::ModuleAccess;
+use vm::errors::{Location, PartialVMError, VMResult};
+use vm::CompiledModule;
+
+/// Returns the constant pool of the given module.
+///
+/// Every constant is returned with its type layout and serialized value, so it can be decoded
+/// without executing the module.
+pub fn module_constants(blob: &[u8]) -> VMResult<Vec<(MoveTypeLayout, Vec<u8>)>> {
+    let module =
+        CompiledModule::deserialize(blob).map_err(|err| err.finish(Location::Undefined))?;
+    module
+        .constant_pool()
+        .iter()
+        .map(|constant| {
+            let layout = constant.layout().ok_or_else(|| {
+                PartialVMError::new(StatusCode::INVALID_CONSTANT_TYPE).finish(Location::Undefined)
+            })?;
+            Ok((layout, constant.data.clone()))
+        })
+        .collect()
+}
@@ -1,15 +1,29 @@
 use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
+use anyhow::{anyhow, Error};
+use core::cell::RefCell;
+use hashbrown::HashMap;
 
 use move_core_types::account_address::AccountAddress;
-use move_core_types::language_storage::{ModuleId, StructTag, TypeTag, CORE_CODE_ADDRESS};
+use move_core_types::identifier::Identifier;
+use move_core_types::language_storage::{
+    ModuleId, StructTag, TypeTag, CODE_TAG, CORE_CODE_ADDRESS,
+};
+use move_core_types::value::{MoveTypeLayout, MoveValue};
 use move_core_types::vm_status::StatusCode;
 use move_vm_runtime::data_cache::RemoteCache;
-use move_vm_types::natives::balance::{Balance, NativeBalance, WalletId};
+use move_vm_runtime::logging::NoContextLog;
+use move_vm_types::natives::balance::{Balance, BalanceOperation, NativeBalance, WalletId};
 use move_vm_types::natives::function::PartialVMError;
 use vm::errors::{Location, PartialVMResult, VMError, VMResult};
 
+use crate::access_path::AccessPath;
+use crate::memory::{InMemoryOracle, InMemoryStorage};
+
 pub trait Storage {
     /// Returns the data for `key` in the storage or `None` if the key can not be found.
     fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
@@ -17,8 +31,91 @@ pub trait Storage {
     fn insert(&self, key: &[u8], value: &[u8]);
     /// Clear the storage of the given `key` and its value.
     fn remove(&self, key: &[u8]);
+    /// Returns `true` if the storage contains `key`.
+    ///
+    /// Storages with a cheap existence check should override this to avoid loading the value.
+    fn contains(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+    /// Returns the data for each of `keys`, in the same order.
+    ///
+    /// Storages with a native batched read should override this to make a single round-trip.
+    fn multi_get(&self, keys: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+}
+
+/// Storage which can enumerate its keys.
+pub trait StorageIter: Storage {
+    /// Returns all key-value pairs whose key starts with `prefix`.
+    fn scan_prefix(&self, prefix: &[u8]) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + '_>;
+
+    /// Returns all keys starting with `prefix`.
+    ///
+    /// Storages which can enumerate keys without loading the values should override this.
+    fn scan_keys(&self, prefix: &[u8]) -> Box<dyn Iterator<Item = Vec<u8>> + '_> {
+        Box::new(self.scan_prefix(prefix).map(|(key, _)| key))
+    }
+}
+
+impl<S: Storage + ?Sized> Storage for &S {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        (**self).get(key)
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) {
+        (**self).insert(key, value)
+    }
+
+    fn remove(&self, key: &[u8]) {
+        (**self).remove(key)
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        (**self).contains(key)
+    }
+
+    fn multi_get(&self, keys: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
+        (**self).multi_get(keys)
+    }
 }
 
+impl<S: StorageIter + ?Sized> StorageIter for &S {
+    fn scan_prefix(&self, prefix: &[u8]) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + '_> {
+        (**self).scan_prefix(prefix)
+    }
+
+    fn scan_keys(&self, prefix: &[u8]) -> Box<dyn Iterator<Item = Vec<u8>> + '_> {
+        (**self).scan_keys(prefix)
+    }
+}
+
+/// Host policy for addresses which transactions may write resources to.
+pub trait AddressPolicy {
+    /// Returns `true` if resources under `address` may be written.
+    fn allow_write(&self, address: &AccountAddress) -> bool;
+}
+
+/// Address policy which allows writes to any address.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AllowAllAddresses;
+
+impl AddressPolicy for AllowAllAddresses {
+    fn allow_write(&self, _address: &AccountAddress) -> bool {
+        true
+    }
+}
+
+/// Host sink for execution logs of the vm.
+pub trait ContextLogger {
+    /// Called by the vm on critical errors, such as invariant violations.
+    fn alert(&self) {}
+    /// Called with the error of every failed transaction.
+    fn on_error(&self, _sender: &AccountAddress, _err: &VMError) {}
+}
+
+impl ContextLogger for NoContextLog {}
+
 pub trait WriteEffects {
     fn delete(&self, path: AccessKey);
     fn insert(&self, path: AccessKey, blob: Vec<u8>);
@@ -27,6 +124,65 @@ pub trait WriteEffects {
 pub struct State<S, O: Oracle> {
     store: S,
     oracle: OracleView<O>,
+    module_cache: RefCell<ModuleCache>,
+}
+
+/// Full copy of the storage content taken by `State::snapshot`, ordered by key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateSnapshot(Vec<(Vec<u8>, Vec<u8>)>);
+
+impl StateSnapshot {
+    /// Returns the number of stored keys.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the snapshot contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Least recently used cache of module blobs keyed by their storage key.
+struct ModuleCache {
+    capacity: usize,
+    entries: VecDeque<(Vec<u8>, Vec<u8>)>,
+}
+
+impl ModuleCache {
+    fn new(capacity: usize) -> ModuleCache {
+        ModuleCache {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn get(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let pos = self.entries.iter().position(|(k, _)| k.as_slice() == key)?;
+        let entry = self.entries.remove(pos)?;
+        let blob = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(blob)
+    }
+
+    fn put(&mut self, key: Vec<u8>, blob: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.invalidate(&key);
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, blob));
+    }
+
+    fn invalidate(&mut self, key: &[u8]) {
+        self.entries.retain(|(k, _)| k.as_slice() != key);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
 }
 
 pub trait EventHandler {
@@ -45,11 +201,172 @@ where
     O: Oracle,
 {
     pub fn new(store: S, oracle: O) -> State<S, O> {
+        State::with_cache_capacity(store, oracle, 0)
+    }
+
+    /// Creates a state which keeps up to `capacity` recently loaded modules in memory.
+    pub fn with_cache_capacity(store: S, oracle: O, capacity: usize) -> State<S, O> {
         State {
             store,
             oracle: OracleView::new(oracle),
+            module_cache: RefCell::new(ModuleCache::new(capacity)),
         }
     }
+
+    /// Sets the number of recently loaded modules kept in memory and clears the cache.
+    pub fn set_cache_capacity(&mut self, capacity: usize) {
+        self.module_cache = RefCell::new(ModuleCache::new(capacity));
+    }
+
+    /// Returns the underlying storage.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the underlying oracle.
+    pub fn oracle(&self) -> &O {
+        self.oracle.inner()
+    }
+
+    /// Loads several resources stored under `address` with a single storage read.
+    ///
+    /// The result has one entry per tag, in the order of `tags`.
+    pub fn get_resources(
+        &self,
+        address: &AccountAddress,
+        tags: &[StructTag],
+    ) -> PartialVMResult<Vec<Option<Vec<u8>>>> {
+        let keys = tags
+            .iter()
+            .map(|tag| AccessKey::from((address, tag)).0)
+            .collect::<Vec<_>>();
+        let mut blobs = self.store.multi_get(&keys);
+        if blobs.len() != tags.len() {
+            return Err(
+                PartialVMError::new(StatusCode::STORAGE_ERROR).with_message(String::from(
+                    "Storage returned an unexpected number of values",
+                )),
+            );
+        }
+
+        if address == &CORE_CODE_ADDRESS {
+            for (tag, blob) in tags.iter().zip(blobs.iter_mut()) {
+                if let Some(ticker) = self.oracle.get_ticker(tag) {
+                    *blob = self.oracle.get_price(&ticker);
+                }
+            }
+        }
+        Ok(blobs)
+    }
+
+    /// Returns `true` if the resource exists, without loading its blob from the storage.
+    pub fn resource_exists(&self, address: &AccountAddress, tag: &StructTag) -> bool {
+        if address == &CORE_CODE_ADDRESS {
+            if let Some(ticker) = self.oracle.get_ticker(tag) {
+                return self.oracle.get_price(&ticker).is_some();
+            }
+        }
+
+        self.store
+            .contains(AccessKey::from((address, tag)).as_ref())
+    }
+
+    /// Loads the resource and decodes it into a `MoveValue` tree using the given layout.
+    pub fn get_resource_decoded(
+        &self,
+        addr: &AccountAddress,
+        tag: &StructTag,
+        layout: &MoveTypeLayout,
+    ) -> Result<Option<MoveValue>, Error> {
+        self.get_resource(addr, tag)
+            .map_err(|err| anyhow!("Failed to load resource {}: {:?}", tag, err))?
+            .map(|blob| MoveValue::simple_deserialize(&blob, layout))
+            .transpose()
+    }
+
+    /// Writes resources, e.g. from a state dump, into the storage.
+    ///
+    /// Blobs are stored as is, without checking them against the resource layouts.
+    pub fn import_state(
+        &self,
+        entries: impl IntoIterator<Item = (AccountAddress, StructTag, Vec<u8>)>,
+    ) {
+        for (address, tag, blob) in entries {
+            self.insert(AccessKey::from((&address, &tag)), blob);
+        }
+    }
+
+    /// Writes modules, e.g. from a state dump, into the storage without verifying them.
+    pub fn import_modules(&self, modules: impl IntoIterator<Item = (ModuleId, Vec<u8>)>) {
+        for (id, blob) in modules {
+            self.insert(AccessKey::from(&id), blob);
+        }
+    }
+}
+
+impl<'a, S, O> State<&'a S, &'a O>
+where
+    S: Storage,
+    O: Oracle,
+{
+    /// Creates a state which borrows the storage and the oracle instead of owning them.
+    pub fn view(store: &'a S, oracle: &'a O) -> State<&'a S, &'a O> {
+        State::new(store, oracle)
+    }
+}
+
+impl<S, O> State<S, O>
+where
+    S: StorageIter,
+    O: Oracle,
+{
+    /// Captures every key-value pair of the underlying storage.
+    pub fn snapshot(&self) -> StateSnapshot {
+        let mut entries = self.store.scan_prefix(&[]).collect::<Vec<_>>();
+        entries.sort();
+        StateSnapshot(entries)
+    }
+
+    /// Replaces the content of the underlying storage with the snapshot.
+    pub fn restore(&self, snapshot: StateSnapshot) {
+        let keys = self
+            .store
+            .scan_prefix(&[])
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        for key in keys {
+            self.store.remove(&key);
+        }
+        for (key, value) in snapshot.0 {
+            self.store.insert(&key, &value);
+        }
+        self.module_cache.borrow_mut().clear();
+    }
+
+    /// Returns all resources stored under the given address.
+    pub fn resources_under(&self, addr: &AccountAddress) -> Vec<(StructTag, Vec<u8>)> {
+        self.store
+            .scan_prefix(addr.as_ref())
+            .filter_map(|(key, blob)| {
+                let path = AccessPath::decode_key(&key).ok()?;
+                Some((path.resource_tag()?, blob))
+            })
+            .collect()
+    }
+
+    /// Returns names and blobs of all modules published under the given address.
+    pub fn modules_under(&self, addr: &AccountAddress) -> Vec<(Identifier, Vec<u8>)> {
+        let mut prefix = Vec::with_capacity(1 + AccountAddress::LENGTH);
+        prefix.push(CODE_TAG);
+        prefix.extend_from_slice(addr.as_ref());
+        self.store
+            .scan_prefix(&prefix)
+            .filter_map(|(key, blob)| {
+                let id = AccessPath::decode_key(&key).ok()?.module_id()?;
+                Some((id.name().to_owned(), blob))
+            })
+            .collect()
+    }
 }
 
 impl<S, O> RemoteCache for State<S, O>
@@ -58,7 +375,15 @@ where
     O: Oracle,
 {
     fn get_module(&self, module_id: &ModuleId) -> VMResult<Option<Vec<u8>>> {
-        Ok(self.store.get(AccessKey::from(module_id).as_ref()))
+        let key = AccessKey::from(module_id);
+        if let Some(blob) = self.module_cache.borrow_mut().get(key.as_ref()) {
+            return Ok(Some(blob));
+        }
+        let blob = self.store.get(key.as_ref());
+        if let Some(blob) = &blob {
+            self.module_cache.borrow_mut().put(key.0, blob.clone());
+        }
+        Ok(blob)
     }
 
     fn get_resource(
@@ -82,18 +407,133 @@ where
     O: Oracle,
 {
     fn delete(&self, key: AccessKey) {
+        self.module_cache.borrow_mut().invalidate(key.as_ref());
         self.store.remove(key.as_ref());
     }
 
     fn insert(&self, key: AccessKey, blob: Vec<u8>) {
+        self.module_cache.borrow_mut().invalidate(key.as_ref());
         self.store.insert(key.as_ref(), &blob);
     }
 }
 
+/// Storage writes buffered until the whole write set is known.
+#[derive(Default)]
+pub struct WriteBatch {
+    ops: Vec<(AccessKey, Option<Vec<u8>>)>,
+}
+
+impl WriteBatch {
+    pub fn new() -> WriteBatch {
+        WriteBatch::default()
+    }
+
+    /// Stages insertion of `blob` under `key`.
+    pub fn insert(&mut self, key: AccessKey, blob: Vec<u8>) {
+        self.ops.push((key, Some(blob)));
+    }
+
+    /// Stages removal of `key`.
+    pub fn delete(&mut self, key: AccessKey) {
+        self.ops.push((key, None));
+    }
+
+    /// Returns the number of staged writes.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if nothing is staged.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Returns staged writes as raw storage keys and values, `None` marks a removal.
+    pub fn into_writes(self) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+        self.ops
+            .into_iter()
+            .map(|(key, blob)| (key.0, blob))
+            .collect()
+    }
+
+    /// Applies all staged writes in order.
+    pub fn commit<W: WriteEffects>(self, target: &W) {
+        for (key, blob) in self.ops {
+            match blob {
+                Some(blob) => target.insert(key, blob),
+                None => target.delete(key),
+            }
+        }
+    }
+
+    /// Discards all staged writes.
+    pub fn rollback(self) {}
+}
+
+/// Builds a state with the given modules and resources over in-memory storage.
+#[derive(Default)]
+pub struct StateBuilder {
+    store: InMemoryStorage,
+}
+
+impl StateBuilder {
+    pub fn new() -> StateBuilder {
+        StateBuilder::default()
+    }
+
+    /// Adds module with the given id.
+    pub fn module(self, id: &ModuleId, blob: Vec<u8>) -> StateBuilder {
+        self.store.insert(AccessKey::from(id).as_ref(), &blob);
+        self
+    }
+
+    /// Adds resource under the given address.
+    pub fn resource(
+        self,
+        address: &AccountAddress,
+        tag: &StructTag,
+        blob: Vec<u8>,
+    ) -> StateBuilder {
+        self.store
+            .insert(AccessKey::from((address, tag)).as_ref(), &blob);
+        self
+    }
+
+    /// Adds modules.
+    pub fn modules(self, modules: impl IntoIterator<Item = (ModuleId, Vec<u8>)>) -> StateBuilder {
+        modules
+            .into_iter()
+            .fold(self, |builder, (id, blob)| builder.module(&id, blob))
+    }
+
+    /// Adds resources.
+    pub fn resources(
+        self,
+        resources: impl IntoIterator<Item = (AccountAddress, StructTag, Vec<u8>)>,
+    ) -> StateBuilder {
+        resources
+            .into_iter()
+            .fold(self, |builder, (address, tag, blob)| {
+                builder.resource(&address, &tag, blob)
+            })
+    }
+
+    /// Creates the state.
+    pub fn build(self) -> State<InMemoryStorage, InMemoryOracle> {
+        State::new(self.store, InMemoryOracle::default())
+    }
+}
+
 pub trait Oracle {
     fn get_price(&self, ticker: &str) -> Option<u128>;
 }
 
+impl<O: Oracle + ?Sized> Oracle for &O {
+    fn get_price(&self, ticker: &str) -> Option<u128> {
+        (**self).get_price(ticker)
+    }
+}
+
 pub struct OracleView<O: Oracle> {
     oracle: O,
 }
@@ -109,6 +549,10 @@ where
         OracleView { oracle }
     }
 
+    pub fn inner(&self) -> &O {
+        &self.oracle
+    }
+
     pub fn get_ticker(&self, tag: &StructTag) -> Option<String> {
         fn extract_name(tag: &TypeTag) -> Option<String> {
             match tag {
@@ -149,17 +593,105 @@ where
     }
 }
 
+/// Source of synthetic resources served to transactions instead of stored ones.
+pub trait Resolve {
+    /// Returns `Some` if the resolver serves the resource, or `None` to pass the lookup on.
+    fn resolve(
+        &self,
+        address: &AccountAddress,
+        tag: &StructTag,
+    ) -> PartialVMResult<Option<Vec<u8>>>;
+}
+
+impl<T: Resolve + ?Sized> Resolve for &T {
+    fn resolve(
+        &self,
+        address: &AccountAddress,
+        tag: &StructTag,
+    ) -> PartialVMResult<Option<Vec<u8>>> {
+        (**self).resolve(address, tag)
+    }
+}
+
+impl Resolve for ExecutionContext {
+    fn resolve(
+        &self,
+        address: &AccountAddress,
+        tag: &StructTag,
+    ) -> PartialVMResult<Option<Vec<u8>>> {
+        if address != &CORE_CODE_ADDRESS || tag.address != CORE_CODE_ADDRESS {
+            return Ok(None);
+        }
+
+        let blob = match (tag.module.as_str(), tag.name.as_str()) {
+            ("Block", "BlockMetadata") => Some(self.block_height.to_le_bytes().to_vec()),
+            ("Time", "CurrentTimestamp") => Some(self.current_time().to_le_bytes().to_vec()),
+            ("Transaction", "Sender") => self.origin.map(|origin| origin.to_vec()),
+            ("Block", "BlockHash") => match &self.block_hash {
+                Some(hash) => Some(
+                    bcs::to_bytes(&hash.to_vec())
+                        .map_err(|_| PartialVMError::new(StatusCode::VALUE_SERIALIZATION_ERROR))?,
+                ),
+                None => None,
+            },
+            ("Chain", "ChainId") => self
+                .chain_id
+                .map(|chain_id| chain_id.to_le_bytes().to_vec()),
+            ("Transaction", "GasUnitPrice") => self
+                .gas_unit_price
+                .map(|price| price.to_le_bytes().to_vec()),
+            _ => None,
+        };
+        Ok(blob)
+    }
+}
+
+/// Storage keys read and written by a transaction.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AccessSet {
+    /// Keys of the modules and resources read, including resources served by resolvers.
+    pub reads: BTreeSet<Vec<u8>>,
+    /// Keys of the modules and resources written or removed.
+    pub writes: BTreeSet<Vec<u8>>,
+}
+
 pub struct StateSession<'r, R: RemoteCache> {
     remote: &'r R,
     context: ExecutionContext,
+    resolvers: Vec<Box<dyn Resolve + 'r>>,
+    access_set: RefCell<AccessSet>,
 }
 
-impl<R> StateSession<'_, R>
+impl<'r, R> StateSession<'r, R>
 where
     R: RemoteCache,
 {
-    pub fn new(remote: &R, context: ExecutionContext) -> StateSession<'_, R> {
-        StateSession { remote, context }
+    pub fn new(remote: &'r R, context: ExecutionContext) -> StateSession<'r, R> {
+        StateSession {
+            remote,
+            context,
+            resolvers: vec![],
+            access_set: RefCell::new(AccessSet::default()),
+        }
+    }
+
+    /// Returns the storage keys accessed within the session.
+    ///
+    /// Modules already cached by the vm loader are not read again and are not reported.
+    pub fn access_set(&self) -> AccessSet {
+        self.access_set.borrow().clone()
+    }
+
+    /// Records the key as written by the session.
+    pub(crate) fn record_write(&self, key: Vec<u8>) {
+        self.access_set.borrow_mut().writes.insert(key);
+    }
+
+    /// Adds a resolver which is consulted after the execution context and before the storage.
+    ///
+    /// Resolvers are consulted in the order they were added.
+    pub fn push_resolver(&mut self, resolver: Box<dyn Resolve + 'r>) {
+        self.resolvers.push(resolver);
     }
 }
 
@@ -168,6 +700,10 @@ where
     R: RemoteCache,
 {
     fn get_module(&self, module_id: &ModuleId) -> VMResult<Option<Vec<u8>>> {
+        self.access_set
+            .borrow_mut()
+            .reads
+            .insert(module_key(module_id));
         self.remote.get_module(module_id)
     }
 
@@ -176,21 +712,62 @@ where
         address: &AccountAddress,
         tag: &StructTag,
     ) -> PartialVMResult<Option<Vec<u8>>> {
-        if address == &CORE_CODE_ADDRESS && tag.address == CORE_CODE_ADDRESS {
-            if tag.module.as_str() == "Block" && tag.name.as_str() == "BlockMetadata" {
-                return Ok(Some(self.context.block_height.to_le_bytes().to_vec()));
-            } else if tag.module.as_str() == "Time" && tag.name.as_str() == "CurrentTimestamp" {
-                return Ok(Some(self.context.timestamp.to_le_bytes().to_vec()));
+        self.access_set
+            .borrow_mut()
+            .reads
+            .insert(resource_key(address, tag));
+        if let Some(blob) = self.context.resolve(address, tag)? {
+            return Ok(Some(blob));
+        }
+        for resolver in &self.resolvers {
+            if let Some(blob) = resolver.resolve(address, tag)? {
+                return Ok(Some(blob));
             }
         }
         self.remote.get_resource(address, tag)
     }
 }
 
+/// Unit of the timestamp served as `Time::CurrentTimestamp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Micros,
+    Millis,
+    Seconds,
+}
+
+impl Default for TimeUnit {
+    fn default() -> Self {
+        TimeUnit::Micros
+    }
+}
+
+impl TimeUnit {
+    /// Converts a timestamp in microseconds into this unit.
+    pub fn convert(self, micros: u64) -> u64 {
+        match self {
+            TimeUnit::Micros => micros,
+            TimeUnit::Millis => micros / 1_000,
+            TimeUnit::Seconds => micros / 1_000_000,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ExecutionContext {
+    /// Block timestamp in microseconds.
     pub timestamp: u64,
     pub block_height: u64,
+    /// Address the transaction originates from.
+    pub origin: Option<AccountAddress>,
+    /// Hash of the current block.
+    pub block_hash: Option<[u8; 32]>,
+    /// Identifier of the chain.
+    pub chain_id: Option<u64>,
+    /// Price of a gas unit paid by the transaction.
+    pub gas_unit_price: Option<u64>,
+    /// Unit the timestamp is served in.
+    pub time_unit: TimeUnit,
 }
 
 impl ExecutionContext {
@@ -198,38 +775,218 @@ impl ExecutionContext {
         ExecutionContext {
             timestamp,
             block_height,
+            origin: None,
+            block_hash: None,
+            chain_id: None,
+            gas_unit_price: None,
+            time_unit: TimeUnit::default(),
         }
     }
+
+    /// Sets the address served as `Transaction::Sender`.
+    pub fn with_origin(mut self, origin: AccountAddress) -> ExecutionContext {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Sets the hash served as `Block::BlockHash`.
+    pub fn with_block_hash(mut self, block_hash: [u8; 32]) -> ExecutionContext {
+        self.block_hash = Some(block_hash);
+        self
+    }
+
+    /// Sets the identifier served as `Chain::ChainId`.
+    pub fn with_chain_id(mut self, chain_id: u64) -> ExecutionContext {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Sets the price served as `Transaction::GasUnitPrice`.
+    pub fn with_gas_unit_price(mut self, gas_unit_price: u64) -> ExecutionContext {
+        self.gas_unit_price = Some(gas_unit_price);
+        self
+    }
+
+    /// Sets the unit `Time::CurrentTimestamp` is served in.
+    pub fn with_time_unit(mut self, time_unit: TimeUnit) -> ExecutionContext {
+        self.time_unit = time_unit;
+        self
+    }
+
+    /// Returns the timestamp served as `Time::CurrentTimestamp`.
+    pub fn current_time(&self) -> u64 {
+        self.time_unit.convert(self.timestamp)
+    }
+
+    /// Returns the block height served as `Block::BlockMetadata`.
+    pub fn current_block(&self) -> u64 {
+        self.block_height
+    }
 }
 
+/// Reason why the host rejected a balance operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceError {
+    /// The account holds less than the requested amount.
+    InsufficientFunds,
+    /// The balance can not be changed, e.g. the account is missing or locked.
+    Unavailable,
+    /// The resulting balance would exceed `Balance::MAX`.
+    Overflow,
+}
+
+/// Host balances backing the native coins.
+///
+/// Balance changes must never wrap around: an operation which can not be applied must leave
+/// the balance untouched and return an error, which aborts the transaction with
+/// `NATIVE_BALANCE_OPERATION_FAILED`.
 pub trait BalanceAccess {
     fn get_balance(&self, address: &AccountAddress, ticker: &str) -> Option<Balance>;
-    fn deposit(&self, address: &AccountAddress, ticker: &str, amount: Balance);
-    fn withdraw(&self, address: &AccountAddress, ticker: &str, amount: Balance);
+    /// Moves `amount` from the host balance into the vm.
+    ///
+    /// Returns `BalanceError::InsufficientFunds` if the balance is less than `amount`.
+    fn deposit(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError>;
+    /// Moves `amount` from the vm back to the host balance.
+    ///
+    /// Returns `BalanceError::Overflow` if the resulting balance does not fit into `Balance`.
+    fn withdraw(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError>;
+}
+
+/// Location of the standard library modules which back native balances.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BankConfig {
+    /// Address the standard library is published under.
+    pub std_address: AccountAddress,
+    /// Module whose balances use the module name as ticker.
+    pub pont_module: Identifier,
+    /// Module whose balances use the coin struct name as ticker.
+    pub coins_module: Identifier,
+    /// Gas units charged for every native balance operation performed by a transaction.
+    pub operation_gas: u64,
+    /// Generic coin structs `module::name<Token>`, given as `(module, name)`, whose balances use
+    /// the ticker of the token. They are also found inside structs wrapping them.
+    pub coin_standards: Vec<(Identifier, Identifier)>,
+}
+
+impl BankConfig {
+    /// Configuration of the Pontem standard library.
+    pub fn pontem() -> BankConfig {
+        BankConfig {
+            std_address: CORE_CODE_ADDRESS,
+            pont_module: Identifier::new(PONT).unwrap(),
+            coins_module: Identifier::new(COINS).unwrap(),
+            operation_gas: 0,
+            coin_standards: Vec::new(),
+        }
+    }
+
+    /// Returns the ticker of the balance behind the given wallet.
+    fn ticker<'a>(&self, wallet_id: &'a WalletId) -> Option<&'a str> {
+        self.coin_ticker(&wallet_id.tag)
+            .or_else(|| self.find_in_type(&wallet_id.tag))
+    }
+
+    /// Returns the ticker of the standard library coin.
+    fn coin_ticker<'a>(&self, tag: &'a StructTag) -> Option<&'a str> {
+        if tag.address != self.std_address {
+            None
+        } else if tag.module == self.pont_module {
+            Some(tag.module.as_str())
+        } else if tag.module == self.coins_module {
+            Some(tag.name.as_str())
+        } else {
+            None
+        }
+    }
+
+    /// Looks for a coin standard struct in the type and returns the ticker of its token.
+    fn find_in_type<'a>(&self, tag: &'a StructTag) -> Option<&'a str> {
+        let is_standard = tag.address == self.std_address
+            && self
+                .coin_standards
+                .iter()
+                .any(|(module, name)| &tag.module == module && &tag.name == name);
+        if is_standard {
+            return match tag.type_params.first() {
+                Some(TypeTag::Struct(token)) => self.coin_ticker(token),
+                _ => None,
+            };
+        }
+        tag.type_params.iter().find_map(|param| match param {
+            TypeTag::Struct(inner) => self.find_in_type(inner),
+            _ => None,
+        })
+    }
+}
+
+impl Default for BankConfig {
+    fn default() -> Self {
+        BankConfig::pontem()
+    }
 }
 
 pub struct Bank<B: BalanceAccess> {
     access: B,
+    config: BankConfig,
 }
 
 impl<B: BalanceAccess> Bank<B> {
-    pub fn new(access: B) -> Bank<B> {
-        Bank { access }
+    pub fn new(access: B, config: BankConfig) -> Bank<B> {
+        Bank { access, config }
     }
 
-    pub fn deposit(&self, wallet_id: &WalletId, amount: Balance) -> Result<(), VMError> {
-        if let Some(ticker) = ticker(wallet_id) {
-            self.access.deposit(&wallet_id.address, ticker, amount);
+    /// Replaces the bank configuration.
+    pub fn with_config(self, config: BankConfig) -> Bank<B> {
+        Bank {
+            access: self.access,
+            config,
+        }
+    }
+
+    /// Returns the bank configuration.
+    pub fn config(&self) -> &BankConfig {
+        &self.config
+    }
+
+    /// Returns the underlying balance access.
+    pub fn access(&self) -> &B {
+        &self.access
+    }
+
+    /// Checks that the wallet is backed by a balance known to the bank.
+    pub fn check_wallet(&self, wallet_id: &WalletId) -> Result<(), VMError> {
+        if self.config.ticker(wallet_id).is_some() {
             Ok(())
         } else {
             Err(PartialVMError::new(StatusCode::INTERNAL_TYPE_ERROR).finish(Location::Undefined))
         }
     }
 
+    pub fn deposit(&self, wallet_id: &WalletId, amount: Balance) -> Result<(), VMError> {
+        if let Some(ticker) = self.config.ticker(wallet_id) {
+            self.access
+                .deposit(&wallet_id.address, ticker, amount)
+                .map_err(|err| balance_error(wallet_id, err))
+        } else {
+            Err(PartialVMError::new(StatusCode::INTERNAL_TYPE_ERROR).finish(Location::Undefined))
+        }
+    }
+
     pub fn withdraw(&self, wallet_id: &WalletId, amount: Balance) -> Result<(), VMError> {
-        if let Some(ticker) = ticker(wallet_id) {
-            self.access.withdraw(&wallet_id.address, ticker, amount);
-            Ok(())
+        if let Some(ticker) = self.config.ticker(wallet_id) {
+            self.access
+                .withdraw(&wallet_id.address, ticker, amount)
+                .map_err(|err| balance_error(wallet_id, err))
         } else {
             Err(PartialVMError::new(StatusCode::INTERNAL_TYPE_ERROR).finish(Location::Undefined))
         }
@@ -238,7 +995,7 @@ impl<B: BalanceAccess> Bank<B> {
 
 impl<B: BalanceAccess> NativeBalance for &Bank<B> {
     fn get_balance(&self, wallet_id: &WalletId) -> Option<Balance> {
-        if let Some(ticker) = ticker(wallet_id) {
+        if let Some(ticker) = self.config.ticker(wallet_id) {
             self.access.get_balance(&wallet_id.address, ticker)
         } else {
             None
@@ -246,33 +1003,123 @@ impl<B: BalanceAccess> NativeBalance for &Bank<B> {
     }
 }
 
-fn ticker(wallet_id: &WalletId) -> Option<&str> {
-    if wallet_id.tag.address == CORE_CODE_ADDRESS {
-        match wallet_id.tag.module.as_str() {
-            PONT => Some(PONT),
-            COINS => Some(wallet_id.tag.name.as_str()),
-            _ => None,
+/// Direction of a native balance operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferKind {
+    /// Coins move from the host balance into the vm.
+    Deposit,
+    /// Coins move from the vm back to the host balance.
+    Withdraw,
+}
+
+/// Native balance operation logged by `BankSession`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    /// Owner of the changed balance.
+    pub address: AccountAddress,
+    /// Index of `address` among the transaction signers.
+    ///
+    /// `None` if the balance owner did not sign the transaction, e.g. if the standard library
+    /// created a signer for it.
+    pub signer: Option<usize>,
+    pub ticker: Rc<String>,
+    pub kind: TransferKind,
+    pub amount: Balance,
+}
+
+/// Bank view of a single transaction which logs the balance operations it makes.
+pub struct BankSession<'b, B: BalanceAccess> {
+    bank: &'b Bank<B>,
+    signers: Vec<AccountAddress>,
+    tickers: RefCell<HashMap<String, Rc<String>>>,
+    transfer_log: RefCell<Vec<Transfer>>,
+}
+
+impl<'b, B: BalanceAccess> BankSession<'b, B> {
+    /// Creates a session for a transaction signed by `signers`, in the order of the transaction.
+    pub fn new(bank: &'b Bank<B>, signers: Vec<AccountAddress>) -> BankSession<'b, B> {
+        BankSession {
+            bank,
+            signers,
+            tickers: RefCell::new(HashMap::new()),
+            transfer_log: RefCell::new(Vec::new()),
         }
-    } else {
-        None
     }
+
+    /// Returns every balance operation in execution order.
+    pub fn transfer_log(&self) -> Vec<Transfer> {
+        self.transfer_log.borrow().clone()
+    }
+}
+
+impl<B: BalanceAccess> NativeBalance for &BankSession<'_, B> {
+    fn get_balance(&self, wallet_id: &WalletId) -> Option<Balance> {
+        self.bank.get_balance(wallet_id)
+    }
+
+    fn on_balance_operation(&self, wallet_id: &WalletId, op: &BalanceOperation) {
+        let ticker = match self.bank.config.ticker(wallet_id) {
+            Some(ticker) => ticker,
+            None => return,
+        };
+        let ticker = self
+            .tickers
+            .borrow_mut()
+            .entry(ticker.to_owned())
+            .or_insert_with(|| Rc::new(ticker.to_owned()))
+            .clone();
+        let (kind, amount) = match op {
+            BalanceOperation::Deposit(amount) => (TransferKind::Deposit, *amount),
+            BalanceOperation::Withdraw(amount) => (TransferKind::Withdraw, *amount),
+        };
+        let signer = self
+            .signers
+            .iter()
+            .position(|signer| signer == &wallet_id.address);
+        self.transfer_log.borrow_mut().push(Transfer {
+            address: wallet_id.address,
+            signer,
+            ticker,
+            kind,
+            amount,
+        });
+    }
+}
+
+fn balance_error(wallet_id: &WalletId, err: BalanceError) -> VMError {
+    PartialVMError::new(StatusCode::NATIVE_BALANCE_OPERATION_FAILED)
+        .with_message(format!(
+            "{:?} for wallet {}::{}",
+            err, wallet_id.address, wallet_id.tag
+        ))
+        .finish(Location::Undefined)
+}
+
+/// Returns the storage key of the resource, for reading the storage outside of the vm.
+pub fn resource_key(address: &AccountAddress, tag: &StructTag) -> Vec<u8> {
+    let tag = tag.access_vector();
+    let mut key = Vec::with_capacity(AccountAddress::LENGTH + tag.len());
+    key.extend_from_slice(address.as_ref());
+    key.extend_from_slice(&tag);
+    key
+}
+
+/// Returns the storage key of the module, for reading the storage outside of the vm.
+pub fn module_key(module_id: &ModuleId) -> Vec<u8> {
+    module_id.access_vector()
 }
 
 pub struct AccessKey(Vec<u8>);
 
 impl From<(&AccountAddress, &StructTag)> for AccessKey {
     fn from((addr, tag): (&AccountAddress, &StructTag)) -> Self {
-        let tag = tag.access_vector();
-        let mut key = Vec::with_capacity(AccountAddress::LENGTH + tag.len());
-        key.extend_from_slice(addr.as_ref());
-        key.extend_from_slice(&tag);
-        AccessKey(key)
+        AccessKey(resource_key(addr, tag))
     }
 }
 
 impl From<&ModuleId> for AccessKey {
     fn from(id: &ModuleId) -> Self {
-        AccessKey(id.access_vector())
+        AccessKey(module_key(id))
     }
 }
 
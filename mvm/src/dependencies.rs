@@ -0,0 +1,127 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+
+use hashbrown::HashMap;
+use move_core_types::language_storage::ModuleId;
+use move_core_types::vm_status::StatusCode;
+use vm::access::ModuleAccess;
+use vm::errors::{Location, PartialVMError, VMResult};
+use vm::CompiledModule;
+
+/// Returns identifiers of the modules the given module depends on.
+pub fn module_dependencies(module: &CompiledModule) -> Vec<ModuleId> {
+    let self_handle = module.self_handle();
+    module
+        .module_handles()
+        .iter()
+        .filter(|handle| *handle != self_handle)
+        .map(|handle| module.module_id_for_handle(handle))
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    New,
+    InProgress,
+    Done,
+}
+
+/// Dependency cycle between modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// Modules of the cycle, each one depends on the next and the last one on the first.
+    pub modules: Vec<ModuleId>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cyclic dependency between modules")?;
+        for (i, id) in self.modules.iter().enumerate() {
+            write!(f, "{} {}", if i == 0 { ":" } else { "," }, id)?;
+        }
+        write!(f, ".")
+    }
+}
+
+/// Returns indexes of the modules ordered so that every module follows its dependencies from
+/// the same slice.
+///
+/// Modules which are already in a valid order keep their relative positions.
+/// Dependencies outside of the slice are ignored.
+pub fn topo_sort_modules(modules: &[CompiledModule]) -> Result<Vec<usize>, CycleError> {
+    let ids: Vec<ModuleId> = modules.iter().map(|module| module.self_id()).collect();
+    let index: HashMap<&ModuleId, usize> = ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+    let deps: Vec<Vec<usize>> = modules
+        .iter()
+        .map(|module| {
+            module_dependencies(module)
+                .iter()
+                .filter_map(|id| index.get(id).cloned())
+                .collect()
+        })
+        .collect();
+
+    fn visit(
+        node: usize,
+        deps: &[Vec<usize>],
+        ids: &[ModuleId],
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), CycleError> {
+        match marks[node] {
+            Mark::Done => Ok(()),
+            Mark::InProgress => {
+                let start = path.iter().position(|n| *n == node).unwrap_or(0);
+                Err(CycleError {
+                    modules: path[start..].iter().map(|n| ids[*n].clone()).collect(),
+                })
+            }
+            Mark::New => {
+                marks[node] = Mark::InProgress;
+                path.push(node);
+                for dep in &deps[node] {
+                    visit(*dep, deps, ids, marks, path, order)?;
+                }
+                path.pop();
+                marks[node] = Mark::Done;
+                order.push(node);
+                Ok(())
+            }
+        }
+    }
+
+    let mut marks = vec![Mark::New; modules.len()];
+    let mut path = Vec::new();
+    let mut order = Vec::with_capacity(modules.len());
+    for node in 0..modules.len() {
+        visit(node, &deps, &ids, &mut marks, &mut path, &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Orders package modules so that every module follows its dependencies from the same package.
+///
+/// Modules which are already in a valid order keep their relative positions.
+/// Dependencies outside of the package are left for the loader to resolve.
+pub fn sort_by_dependencies(modules: Vec<Vec<u8>>) -> VMResult<Vec<Vec<u8>>> {
+    let compiled = modules
+        .iter()
+        .map(|module| {
+            CompiledModule::deserialize(module).map_err(|err| err.finish(Location::Undefined))
+        })
+        .collect::<VMResult<Vec<_>>>()?;
+
+    let order = topo_sort_modules(&compiled).map_err(|err| {
+        PartialVMError::new(StatusCode::LINKER_ERROR)
+            .with_message(err.to_string())
+            .finish(Location::Module(err.modules[0].clone()))
+    })?;
+
+    let mut modules: Vec<Option<Vec<u8>>> = modules.into_iter().map(Some).collect();
+    Ok(order
+        .into_iter()
+        .filter_map(|node| modules[node].take())
+        .collect())
+}
@@ -4,11 +4,15 @@
 extern crate alloc;
 
 use crate::data::ExecutionContext;
-use crate::types::{Gas, ModuleTx, PublishPackageTx, ScriptTx, VmResult};
+use crate::types::{FunctionTx, Gas, ModuleTx, PublishPackageTx, ScriptTx, VmResult};
 
 pub mod access_path;
+pub mod compatibility;
+pub mod constants;
 pub mod data;
+pub mod dependencies;
 pub mod gas_schedule;
+pub mod memory;
 pub mod mvm;
 pub mod types;
 pub mod vm_config;
@@ -31,6 +35,14 @@ pub trait Vm {
         tx: ScriptTx,
         dry_run: bool,
     ) -> VmResult;
+    /// Execute entry function of a published module.
+    fn execute_function(
+        &self,
+        gas: Gas,
+        context: ExecutionContext,
+        tx: FunctionTx,
+        dry_run: bool,
+    ) -> VmResult;
     /// Clear vm cache.
     fn clear(&self);
 }
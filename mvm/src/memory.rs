@@ -0,0 +1,245 @@
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use hashbrown::HashMap;
+
+use move_core_types::account_address::AccountAddress;
+use move_core_types::language_storage::{ModuleId, TypeTag};
+use move_vm_types::natives::balance::Balance;
+
+use crate::data::{BalanceAccess, BalanceError, EventHandler, Oracle, Storage, StorageIter};
+use crate::types::Event;
+
+/// Storage kept in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryStorage {
+    data: RefCell<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl InMemoryStorage {
+    /// Returns the number of stored keys.
+    pub fn len(&self) -> usize {
+        self.data.borrow().len()
+    }
+
+    /// Returns `true` if the storage contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.data.borrow().is_empty()
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.data.borrow().get(key).cloned()
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) {
+        self.data
+            .borrow_mut()
+            .insert(key.to_owned(), value.to_owned());
+    }
+
+    fn remove(&self, key: &[u8]) {
+        self.data.borrow_mut().remove(key);
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        self.data.borrow().contains_key(key)
+    }
+}
+
+/// Storage which buffers writes in memory on top of a read-only base storage.
+///
+/// Used to execute transactions speculatively: the base storage is never modified, and the
+/// accumulated changes can be extracted with `into_changes` or dropped with `discard`.
+pub struct OverlayStore<S: Storage> {
+    base: S,
+    overlay: RefCell<HashMap<Vec<u8>, Option<Vec<u8>>>>,
+}
+
+impl<S: Storage> OverlayStore<S> {
+    /// Creates an overlay with no changes over `base`.
+    pub fn new(base: S) -> OverlayStore<S> {
+        OverlayStore {
+            base,
+            overlay: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the underlying storage.
+    pub fn base(&self) -> &S {
+        &self.base
+    }
+
+    /// Returns the buffered changes. `None` marks a removed key.
+    pub fn into_changes(self) -> HashMap<Vec<u8>, Option<Vec<u8>>> {
+        self.overlay.into_inner()
+    }
+
+    /// Drops all buffered changes.
+    pub fn discard(&self) {
+        self.overlay.borrow_mut().clear();
+    }
+}
+
+impl<S: Storage> Storage for OverlayStore<S> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.overlay.borrow().get(key) {
+            Some(value) => value.clone(),
+            None => self.base.get(key),
+        }
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) {
+        self.overlay
+            .borrow_mut()
+            .insert(key.to_owned(), Some(value.to_owned()));
+    }
+
+    fn remove(&self, key: &[u8]) {
+        self.overlay.borrow_mut().insert(key.to_owned(), None);
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        match self.overlay.borrow().get(key) {
+            Some(value) => value.is_some(),
+            None => self.base.contains(key),
+        }
+    }
+}
+
+impl StorageIter for InMemoryStorage {
+    fn scan_prefix(&self, prefix: &[u8]) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + '_> {
+        let data = self.data.borrow();
+        let entries = data
+            .range(prefix.to_owned()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        Box::new(entries.into_iter())
+    }
+
+    fn scan_keys(&self, prefix: &[u8]) -> Box<dyn Iterator<Item = Vec<u8>> + '_> {
+        let data = self.data.borrow();
+        let keys = data
+            .range(prefix.to_owned()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+        Box::new(keys.into_iter())
+    }
+}
+
+/// Event handler which collects events in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryEventHandler {
+    events: RefCell<Vec<Event>>,
+}
+
+impl InMemoryEventHandler {
+    /// Returns collected events in emission order.
+    pub fn events(&self) -> Vec<Event> {
+        self.events.borrow().clone()
+    }
+
+    /// Removes and returns collected events.
+    pub fn take(&self) -> Vec<Event> {
+        self.events.replace(Vec::new())
+    }
+}
+
+impl EventHandler for InMemoryEventHandler {
+    fn on_event(
+        &self,
+        address: AccountAddress,
+        ty_tag: TypeTag,
+        message: Vec<u8>,
+        caller: Option<ModuleId>,
+    ) {
+        self.events
+            .borrow_mut()
+            .push((address, ty_tag, message, caller));
+    }
+}
+
+/// Oracle with prices kept in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryOracle {
+    prices: RefCell<BTreeMap<String, u128>>,
+}
+
+impl InMemoryOracle {
+    /// Sets price for the given ticker.
+    pub fn set_price(&self, ticker: &str, price: u128) {
+        self.prices.borrow_mut().insert(ticker.to_owned(), price);
+    }
+}
+
+impl Oracle for InMemoryOracle {
+    fn get_price(&self, ticker: &str) -> Option<u128> {
+        self.prices.borrow().get(ticker).cloned()
+    }
+}
+
+/// Native balances kept in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryBank {
+    balances: RefCell<BTreeMap<(AccountAddress, String), Balance>>,
+}
+
+impl InMemoryBank {
+    /// Sets balance of the given account.
+    pub fn set_balance(&self, address: &AccountAddress, ticker: &str, amount: Balance) {
+        self.balances
+            .borrow_mut()
+            .insert((*address, ticker.to_owned()), amount);
+    }
+}
+
+impl BalanceAccess for InMemoryBank {
+    fn get_balance(&self, address: &AccountAddress, ticker: &str) -> Option<Balance> {
+        self.balances
+            .borrow()
+            .get(&(*address, ticker.to_owned()))
+            .cloned()
+    }
+
+    fn deposit(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError> {
+        let mut balances = self.balances.borrow_mut();
+        let balance = balances
+            .get_mut(&(*address, ticker.to_owned()))
+            .ok_or(BalanceError::Unavailable)?;
+        *balance = balance
+            .checked_sub(amount)
+            .ok_or(BalanceError::InsufficientFunds)?;
+        Ok(())
+    }
+
+    fn withdraw(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError> {
+        let mut balances = self.balances.borrow_mut();
+        match balances.get_mut(&(*address, ticker.to_owned())) {
+            Some(balance) => {
+                *balance = balance.checked_add(amount).ok_or(BalanceError::Overflow)?;
+            }
+            // Coins moved out of the vm open the host wallet.
+            None => {
+                balances.insert((*address, ticker.to_owned()), amount);
+            }
+        }
+        Ok(())
+    }
+}
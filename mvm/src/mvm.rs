@@ -1,29 +1,51 @@
 use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::cell::{Ref, RefCell};
+use core::mem;
 
 use anyhow::Error;
+use hashbrown::{HashMap, HashSet};
 
 use move_core_types::account_address::AccountAddress;
 use move_core_types::gas_schedule::CostTable;
 use move_core_types::gas_schedule::{AbstractMemorySize, GasAlgebra, GasUnits};
 use move_core_types::identifier::Identifier;
-use move_core_types::language_storage::{StructTag, TypeTag, CORE_CODE_ADDRESS, NONE_ADDRESS};
+use move_core_types::language_storage::{
+    ModuleId, StructTag, TypeTag, CODE_TAG, CORE_CODE_ADDRESS, NONE_ADDRESS,
+};
+use move_core_types::value::MoveTypeLayout;
 use move_core_types::vm_status::{AbortLocation, StatusCode, VMStatus};
 use move_vm_runtime::data_cache::{RemoteCache, TransactionEffects};
-use move_vm_runtime::logging::NoContextLog;
+use move_vm_runtime::logging::{LogContext, NoContextLog};
 use move_vm_runtime::move_vm::MoveVM;
 use move_vm_runtime::session::Session;
 use move_vm_types::gas_schedule::CostStrategy;
-use move_vm_types::natives::balance::{BalanceOperation, NativeBalance};
+use move_vm_types::natives::balance::{BalanceOperation, NativeBalance, WalletId};
+use move_vm_types::values::Value;
+use vm::access::ModuleAccess;
 use vm::errors::{Location, PartialVMError, VMError, VMResult};
+use vm::file_format::{Bytecode, CompiledScript};
+use vm::normalized::Module;
+use vm::CompiledModule;
 
-use crate::data::AccessKey;
+use crate::access_path::AccessPath;
+use crate::compatibility::is_compatible;
+use crate::data::{module_key, resource_key, AccessKey};
 use crate::data::{
-    BalanceAccess, Bank, EventHandler, ExecutionContext, Oracle, State, StateSession, Storage,
-    WriteEffects,
+    AccessSet, AddressPolicy, AllowAllAddresses, BalanceAccess, Bank, BankConfig, BankSession,
+    ContextLogger, EventHandler, ExecutionContext, Oracle, Resolve, State, StateSession, Storage,
+    StorageIter, Transfer, WriteBatch,
+};
+use crate::dependencies::{module_dependencies, sort_by_dependencies};
+use crate::memory::{InMemoryBank, InMemoryEventHandler, InMemoryOracle, InMemoryStorage};
+use crate::types::{
+    EffectToggles, Event, FunctionTx, Gas, GasBreakdown, ModuleTx, OpcodePolicy, PublishPackageTx,
+    RepublishPolicy, ScriptTx, VmResult, GAS_AMOUNT_MAX_VALUE,
 };
-use crate::types::{Gas, ModuleTx, PublishPackageTx, ScriptTx, VmResult};
-use crate::vm_config::loader::load_vm_config;
+use crate::vm_config::loader::{load_vm_config, store_vm_config};
+use crate::vm_config::VmConfig;
 use crate::Vm;
 
 /// MoveVM.
@@ -35,12 +57,47 @@ where
     B: BalanceAccess,
 {
     vm: MoveVM,
-    cost_table: CostTable,
+    cost_table: RefCell<CostTable>,
     state: State<S, O>,
     event_handler: E,
     bank: Bank<B>,
+    toggles: EffectToggles,
+    republish_policy: RepublishPolicy,
+    address_policy: Box<dyn AddressPolicy>,
+    opcode_policy: OpcodePolicy,
+    event_filter: Option<Box<dyn Fn(&TypeTag) -> bool>>,
+    logger: Box<dyn ContextLogger>,
+    resolvers: Vec<Box<dyn Resolve>>,
+    max_module_bytes: u64,
+    max_type_depth: usize,
+    event_byte_gas: u64,
 }
 
+/// Log context forwarding alerts of the move vm to the `ContextLogger`.
+#[derive(Clone, Copy)]
+struct LoggerContext<'a>(&'a dyn ContextLogger);
+
+impl LogContext for LoggerContext<'_> {
+    fn alert(&self) {
+        self.0.alert();
+    }
+}
+
+/// Resource changes produced by a transaction.
+pub type Resources = Vec<(
+    AccountAddress,
+    Vec<(StructTag, Option<(MoveTypeLayout, Value)>)>,
+)>;
+
+/// Events produced by a transaction.
+pub type Events = Vec<(
+    AccountAddress,
+    TypeTag,
+    MoveTypeLayout,
+    Value,
+    Option<ModuleId>,
+)>;
+
 impl<S, E, O, B> Mvm<S, E, O, B>
 where
     S: Storage,
@@ -56,91 +113,430 @@ where
         balance: B,
     ) -> Result<Mvm<S, E, O, B>, Error> {
         let config = load_vm_config(&store)?;
+        Ok(Self::from_config(
+            store,
+            event_handler,
+            oracle,
+            balance,
+            config,
+        ))
+    }
+
+    /// Creates a new move vm with the given gas schedule instead of the one from the store.
+    ///
+    /// Useful at genesis, before the on-chain configuration is published.
+    pub fn with_cost_table(
+        store: S,
+        event_handler: E,
+        oracle: O,
+        balance: B,
+        table: CostTable,
+    ) -> Mvm<S, E, O, B> {
+        let config = VmConfig {
+            gas_schedule: table,
+            ..VmConfig::default()
+        };
+        Self::from_config(store, event_handler, oracle, balance, config)
+    }
 
-        Ok(Mvm {
-            vm: MoveVM::new(),
-            cost_table: config.gas_schedule,
+    fn from_config(
+        store: S,
+        event_handler: E,
+        oracle: O,
+        balance: B,
+        config: VmConfig,
+    ) -> Mvm<S, E, O, B> {
+        Mvm {
+            vm: MoveVM::with_max_type_depth(config.max_type_depth as usize),
+            cost_table: RefCell::new(config.gas_schedule),
             state: State::new(store, oracle),
             event_handler,
-            bank: Bank::new(balance),
-        })
+            bank: Bank::new(balance, BankConfig::default()),
+            toggles: EffectToggles::default(),
+            republish_policy: RepublishPolicy::default(),
+            address_policy: Box::new(AllowAllAddresses),
+            opcode_policy: OpcodePolicy::default(),
+            event_filter: None,
+            logger: Box::new(NoContextLog::new()),
+            resolvers: Vec::new(),
+            max_module_bytes: config.max_module_bytes,
+            max_type_depth: config.max_type_depth as usize,
+            event_byte_gas: 0,
+        }
+    }
+
+    /// Returns the gas schedule used by the vm.
+    pub fn cost_table(&self) -> Ref<'_, CostTable> {
+        self.cost_table.borrow()
+    }
+
+    /// Replaces the gas schedule used by the vm.
+    ///
+    /// The new schedule applies starting from the next transaction. Loaded modules stay cached.
+    pub fn update_cost_table(&self, table: CostTable) {
+        *self.cost_table.borrow_mut() = table;
+    }
+
+    /// Stores the gas schedule to the on-chain vm configuration.
+    ///
+    /// The new schedule is picked up by vms created after this call.
+    pub fn store_cost_table(&self, table: &CostTable) -> Result<(), VMError> {
+        let mut config = load_vm_config(self.state.store()).map_err(|err| {
+            PartialVMError::new(StatusCode::STORAGE_ERROR)
+                .with_message(format!("{:?}", err))
+                .finish(Location::Undefined)
+        })?;
+        config.gas_schedule = table.clone();
+        store_vm_config(self.state.store(), &config);
+        Ok(())
+    }
+
+    /// Returns the underlying storage.
+    pub fn store(&self) -> &S {
+        self.state.store()
+    }
+
+    /// Returns the event handler.
+    pub fn event_handler(&self) -> &E {
+        &self.event_handler
+    }
+
+    /// Returns the oracle.
+    pub fn oracle(&self) -> &O {
+        self.state.oracle()
+    }
+
+    /// Returns the native balance access.
+    pub fn balances(&self) -> &B {
+        self.bank.access()
+    }
+
+    /// Sets how to handle publishing of already existing modules.
+    pub fn with_republish_policy(mut self, policy: RepublishPolicy) -> Self {
+        self.republish_policy = policy;
+        self
+    }
+
+    /// Sets the policy consulted before resources are written to an address.
+    pub fn with_address_policy<P: AddressPolicy + 'static>(mut self, policy: P) -> Self {
+        self.address_policy = Box::new(policy);
+        self
+    }
+
+    /// Sets where the standard library backing native balances is published.
+    pub fn with_bank_config(mut self, config: BankConfig) -> Self {
+        self.bank = self.bank.with_config(config);
+        self
+    }
+
+    /// Sets instructions which published modules and executed scripts must not contain.
+    pub fn with_opcode_policy(mut self, policy: OpcodePolicy) -> Self {
+        self.opcode_policy = policy;
+        self
+    }
+
+    /// Sets the number of recently loaded modules kept in memory between storage reads.
+    pub fn with_module_cache_capacity(mut self, capacity: usize) -> Self {
+        self.state.set_cache_capacity(capacity);
+        self
+    }
+
+    /// Sets the predicate deciding which event types are passed to the event handler.
+    ///
+    /// Rejected events are not passed to the handler but are still returned in `VmResult`.
+    pub fn with_event_filter<F: Fn(&TypeTag) -> bool + 'static>(mut self, filter: F) -> Self {
+        self.event_filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Sets the logger receiving vm alerts and errors of failed transactions.
+    ///
+    /// Defaults to `NoContextLog`, which discards everything.
+    pub fn with_logger<L: ContextLogger + 'static>(mut self, logger: L) -> Self {
+        self.logger = Box::new(logger);
+        self
+    }
+
+    /// Adds a resolver of synthetic resources to every script and function session.
+    ///
+    /// Resolvers are consulted after the execution context and before the storage, in the order
+    /// they were added.
+    pub fn with_resolver<R: Resolve + 'static>(mut self, resolver: R) -> Self {
+        self.resolvers.push(Box::new(resolver));
+        self
+    }
+
+    /// Sets gas units charged per byte of the serialized event messages. Defaults to zero.
+    pub fn with_event_byte_gas(mut self, gas: u64) -> Self {
+        self.event_byte_gas = gas;
+        self
+    }
+
+    /// Sets which categories of transaction effects are applied to the local state.
+    pub fn with_effect_toggles(mut self, toggles: EffectToggles) -> Self {
+        self.toggles = toggles;
+        self
     }
 
     /// Stores write set into storage and handle events.
-    fn handle_tx_effects(&self, tx_effects: TransactionEffects) -> Result<(), VMError> {
-        for (addr, vals) in tx_effects.resources {
+    /// Returns serialized events emitted by the transaction.
+    ///
+    /// All effects are prepared before anything is written and balances, the only effects which
+    /// can still fail, are applied first and reverted on failure. So a failure leaves storage,
+    /// event handler and bank untouched.
+    fn handle_tx_effects(
+        &self,
+        tx_effects: TransactionEffects,
+        cost_strategy: &mut CostStrategy,
+        gas_breakdown: &mut GasBreakdown,
+    ) -> Result<Vec<Event>, VMError> {
+        let TransactionEffects {
+            resources,
+            modules,
+            events,
+            wallet_ops,
+        } = tx_effects;
+
+        let mut batch = WriteBatch::new();
+        if self.toggles.resources {
+            self.stage_resources(&mut batch, resources)?;
+        }
+        if self.toggles.modules {
+            Self::stage_modules(&mut batch, modules);
+        }
+        let events = Self::serialize_events(events)?;
+        self.charge_event_gas(cost_strategy, gas_breakdown, &events)?;
+        if self.toggles.balances {
+            for id in wallet_ops.keys() {
+                self.bank.check_wallet(id)?;
+            }
+            self.apply_wallet_ops(wallet_ops)?;
+        }
+
+        batch.commit(&self.state);
+        if self.toggles.events {
+            self.dispatch_events(&events);
+        }
+
+        Ok(events)
+    }
+
+    /// Stores resource changes into storage.
+    pub fn apply_resources(&self, resources: Resources) -> Result<(), VMError> {
+        let mut batch = WriteBatch::new();
+        self.stage_resources(&mut batch, resources)?;
+        batch.commit(&self.state);
+        Ok(())
+    }
+
+    /// Stages resource changes into the write batch.
+    ///
+    /// Resources are staged ordered by address and struct tag, so that replays of a transaction
+    /// produce identical storage writes.
+    fn stage_resources(
+        &self,
+        batch: &mut WriteBatch,
+        mut resources: Resources,
+    ) -> Result<(), VMError> {
+        resources.sort_by(|(left, _), (right, _)| left.cmp(right));
+        for (addr, mut vals) in resources {
+            vals.sort_by(|(left, _), (right, _)| left.cmp(right));
+            if !self.address_policy.allow_write(&addr) {
+                return Err(PartialVMError::new(StatusCode::WRITE_TO_FORBIDDEN_ADDRESS)
+                    .with_message(format!("Writes to {} are forbidden", addr))
+                    .finish(Location::Undefined));
+            }
             for (struct_tag, val_opt) in vals {
                 let ak = AccessKey::from((&addr, &struct_tag));
                 match val_opt {
                     None => {
-                        self.state.delete(ak);
+                        batch.delete(ak);
                     }
                     Some((ty_layout, val)) => {
                         let blob = val.simple_serialize(&ty_layout).ok_or_else(|| {
-                            PartialVMError::new(StatusCode::UNKNOWN_INVARIANT_VIOLATION_ERROR)
+                            PartialVMError::new(StatusCode::VALUE_SERIALIZATION_ERROR)
+                                .with_message(format!(
+                                    "Failed to serialize resource {} under {}",
+                                    struct_tag, addr
+                                ))
                                 .finish(Location::Undefined)
                         })?;
-                        self.state.insert(ak, blob);
+                        batch.insert(ak, blob);
                     }
                 };
             }
         }
+        Ok(())
+    }
+
+    /// Stores published modules into storage.
+    pub fn apply_modules(&self, modules: Vec<(ModuleId, Vec<u8>)>) {
+        let mut batch = WriteBatch::new();
+        Self::stage_modules(&mut batch, modules);
+        batch.commit(&self.state);
+    }
+
+    /// Stages published modules into the write batch.
+    fn stage_modules(batch: &mut WriteBatch, modules: Vec<(ModuleId, Vec<u8>)>) {
+        for (module_id, blob) in modules {
+            batch.insert(AccessKey::from(&module_id), blob);
+        }
+    }
 
-        for (module_id, blob) in tx_effects.modules {
-            self.state.insert(AccessKey::from(&module_id), blob);
+    /// Passes emitted events to the event handler.
+    /// Returns the serialized events.
+    pub fn apply_events(&self, events: Events) -> Result<Vec<Event>, VMError> {
+        let events = Self::serialize_events(events)?;
+        self.dispatch_events(&events);
+        Ok(events)
+    }
+
+    /// Passes serialized events to the event handler in the order they were emitted.
+    fn dispatch_events(&self, events: &[Event]) {
+        for (address, ty_tag, msg, caller) in events {
+            self.emit(*address, ty_tag.clone(), msg.clone(), caller.clone());
         }
+    }
 
-        for (address, ty_tag, ty_layout, val, caller) in tx_effects.events {
+    /// Passes the event to the event handler unless the event filter rejects its type.
+    fn emit(
+        &self,
+        address: AccountAddress,
+        ty_tag: TypeTag,
+        msg: Vec<u8>,
+        caller: Option<ModuleId>,
+    ) {
+        if let Some(filter) = &self.event_filter {
+            if !filter(&ty_tag) {
+                return;
+            }
+        }
+        self.event_handler.on_event(address, ty_tag, msg, caller);
+    }
+
+    /// Serializes event messages.
+    fn serialize_events(events: Events) -> Result<Vec<Event>, VMError> {
+        let mut serialized = Vec::with_capacity(events.len());
+        for (address, ty_tag, ty_layout, val, caller) in events {
             let msg = val.simple_serialize(&ty_layout).ok_or_else(|| {
-                PartialVMError::new(StatusCode::UNKNOWN_INVARIANT_VIOLATION_ERROR)
+                PartialVMError::new(StatusCode::VALUE_SERIALIZATION_ERROR)
+                    .with_message(format!("Failed to serialize event {}", ty_tag))
                     .finish(Location::Undefined)
             })?;
-            self.event_handler.on_event(address, ty_tag, msg, caller);
+            serialized.push((address, ty_tag, msg, caller));
         }
+        Ok(serialized)
+    }
 
-        for (id, op) in tx_effects.wallet_ops.into_iter() {
-            match op {
-                BalanceOperation::Deposit(amount) => self.bank.deposit(&id, amount)?,
-                BalanceOperation::Withdraw(amount) => self.bank.withdraw(&id, amount)?,
+    /// Applies native balance operations to the bank ordered by wallet.
+    ///
+    /// If an operation fails, the operations applied before it are reverted, so the bank is
+    /// either fully updated or left unchanged.
+    pub fn apply_wallet_ops(
+        &self,
+        wallet_ops: HashMap<WalletId, BalanceOperation>,
+    ) -> Result<(), VMError> {
+        let mut wallet_ops = wallet_ops.into_iter().collect::<Vec<_>>();
+        wallet_ops.sort_by(|(left, _), (right, _)| left.cmp(right));
+        for (applied, (id, op)) in wallet_ops.iter().enumerate() {
+            let result = match op {
+                BalanceOperation::Deposit(amount) => self.bank.deposit(id, *amount),
+                BalanceOperation::Withdraw(amount) => self.bank.withdraw(id, *amount),
+            };
+            if let Err(err) = result {
+                self.revert_wallet_ops(&wallet_ops[..applied]);
+                return Err(err);
             }
         }
-
         Ok(())
     }
 
+    /// Reverts applied balance operations in reverse order.
+    fn revert_wallet_ops(&self, wallet_ops: &[(WalletId, BalanceOperation)]) {
+        for (id, op) in wallet_ops.iter().rev() {
+            // Reverting restores a balance the host held before, so it can not fail for a
+            // consistent host.
+            let result = match op {
+                BalanceOperation::Deposit(amount) => self.bank.withdraw(id, *amount),
+                BalanceOperation::Withdraw(amount) => self.bank.deposit(id, *amount),
+            };
+            if let Err(err) = result {
+                log::warn!("Failed to revert balance operation on {}: {:?}", id, err);
+            }
+        }
+    }
+
     /// Handle vm result and return transaction status code.
     fn handle_vm_result(
         &self,
         sender: AccountAddress,
-        cost_strategy: CostStrategy,
+        mut cost_strategy: CostStrategy,
         gas_meta: Gas,
+        mut gas_breakdown: GasBreakdown,
         result: Result<TransactionEffects, VMError>,
         dry_run: bool,
     ) -> VmResult {
-        let gas_used = GasUnits::new(gas_meta.max_gas_amount)
-            .sub(cost_strategy.remaining_gas())
-            .get();
-
-        if dry_run {
-            return match result {
-                Ok(_) => VmResult::new(StatusCode::EXECUTED, None, gas_used),
-                Err(err) => VmResult::new(err.major_status(), err.sub_status(), gas_used),
-            };
-        }
+        // Captured before the event charges below. Storage charges are never part of the
+        // intrinsic and execution gas, so this is the gas left right after execution on every
+        // path, even when a package interleaves storage charges between its modules.
+        let remaining_gas_after_execution = gas_meta
+            .max_gas_amount
+            .saturating_sub(gas_breakdown.intrinsic + gas_breakdown.execution);
 
-        match result.and_then(|e| self.handle_tx_effects(e)) {
-            Ok(_) => VmResult::new(StatusCode::EXECUTED, None, gas_used),
+        let result = if dry_run {
+            result.and_then(|effects| {
+                let events = Self::serialize_events(effects.events)?;
+                self.charge_event_gas(&mut cost_strategy, &mut gas_breakdown, &events)?;
+                Ok(vec![])
+            })
+        } else {
+            result.and_then(|effects| {
+                self.handle_tx_effects(effects, &mut cost_strategy, &mut gas_breakdown)
+            })
+        };
+
+        // The remaining gas can never exceed the budget; clamp it so a metering bug can't
+        // underflow `gas_used`.
+        let remaining_gas = cost_strategy
+            .remaining_gas()
+            .get()
+            .min(gas_meta.max_gas_amount);
+        let gas_used = gas_meta.max_gas_amount - remaining_gas;
+
+        let vm_result = match result {
+            Ok(events) => VmResult::new(StatusCode::EXECUTED, None, gas_used).with_events(events),
             Err(err) => {
-                let status = err.major_status();
-                let sub_status = err.sub_status();
-                if let Err(err) = self.emit_vm_status_event(sender, err.into_vm_status()) {
-                    VmResult::new(status, sub_status, gas_used);
-                    log::warn!("Failed to emit vm status event:{:?}", err);
+                self.logger.on_error(&sender, &err);
+                let vm_result = VmResult::from_error(&err, gas_used);
+                if !dry_run {
+                    if let Err(err) = self.emit_vm_status_event(sender, err.into_vm_status()) {
+                        log::warn!("Failed to emit vm status event:{:?}", err);
+                    }
                 }
-
-                VmResult::new(status, sub_status, gas_used)
+                vm_result
             }
-        }
+        };
+        vm_result
+            .with_remaining_gas_after_execution(remaining_gas_after_execution)
+            .with_gas_breakdown(gas_breakdown)
+            .with_refund(&gas_meta)
+    }
+
+    /// Checks the gas against the bounds of the current cost table, which may differ from the
+    /// defaults `Gas::new` validates against. Rejected transactions are not charged and the
+    /// whole budget is refunded.
+    fn check_gas(cost_table: &CostTable, gas: &Gas) -> Result<(), VmResult> {
+        gas.check(&cost_table.gas_constants).map_err(|err| {
+            let err = PartialVMError::new(err.status_code())
+                .with_message(err.to_string())
+                .finish(Location::Undefined);
+            VmResult::from_error(&err, 0).with_refund(gas)
+        })
+    }
+
+    /// Returns the log context passing vm alerts to the logger.
+    fn log_context(&self) -> LoggerContext<'_> {
+        LoggerContext(&*self.logger)
     }
 
     fn emit_vm_status_event(&self, sender: AccountAddress, status: VMStatus) -> Result<(), Error> {
@@ -167,28 +563,672 @@ where
         let msg = bcs::to_bytes(&status)
             .map_err(|err| Error::msg(format!("Failed to generate event message: {:?}", err)))?;
 
-        self.event_handler.on_event(sender, tag, msg, module);
+        self.emit(sender, tag, msg, module);
         Ok(())
     }
 
+    /// Publishes module within the session and charges gas for the global storage writes.
+    ///
+    /// Every publishing path goes through here, so the module size limit is enforced here too.
+    /// Intrinsic gas for the module size is not charged here. Callers charge it exactly once per
+    /// transaction, so that a module is never paid for twice.
     fn _publish_module<R, NB>(
         &self,
         session: &mut Session<'_, '_, R, NB>,
         module: Vec<u8>,
         sender: AccountAddress,
         cost_strategy: &mut CostStrategy,
-    ) -> VMResult<()>
+        gas_breakdown: &mut GasBreakdown,
+    ) -> VMResult<bool>
     where
         R: RemoteCache,
         NB: NativeBalance,
     {
+        self.check_module_size(&module)?;
+        self.check_module_opcodes(&module)?;
+        let republish = self.check_republish(&module)?;
+        let (result, execution) = metered(cost_strategy, |cost_strategy| {
+            if republish {
+                session.republish_module(module, sender, cost_strategy, &self.log_context())
+            } else {
+                session.publish_module(module, sender, cost_strategy, &self.log_context())
+            }
+        });
+        gas_breakdown.execution += execution;
+
+        let (charged, storage) = metered(cost_strategy, |cost_strategy| {
+            Self::charge_global_write_gas_usage(cost_strategy, session, &sender)
+        });
+        gas_breakdown.storage += storage;
+
+        charged?;
+        result.map(|_| republish)
+    }
+
+    /// Runs `execute` in a new session and applies its effects unless `dry_run` is set.
+    ///
+    /// The first of `signers` is charged for the transaction.
+    #[allow(clippy::too_many_arguments)]
+    fn execute_in_session<F>(
+        &self,
+        gas: Gas,
+        context: ExecutionContext,
+        signers: Vec<AccountAddress>,
+        dry_run: bool,
+        writes: Option<&mut WriteBatch>,
+        access: Option<&mut AccessSet>,
+        transfers: Option<&mut Vec<Transfer>>,
+        execute: F,
+    ) -> VmResult
+    where
+        F: FnOnce(
+            &mut Session<'_, '_, StateSession<'_, State<S, O>>, &BankSession<'_, B>>,
+            &mut CostStrategy,
+        ) -> VMResult<()>,
+    {
+        let sender = signers.get(0).cloned().unwrap_or(NONE_ADDRESS);
+        let mut state_session = StateSession::new(&self.state, context);
+        for resolver in &self.resolvers {
+            state_session.push_resolver(Box::new(resolver.as_ref()));
+        }
+        let bank_session = BankSession::new(&self.bank, signers);
+        let mut session = self.vm.new_session(&state_session, &bank_session);
+
+        let cost_table = self.cost_table.borrow();
+        if let Err(rejected) = Self::check_gas(&cost_table, &gas) {
+            return rejected;
+        }
+        let mut cost_strategy =
+            CostStrategy::transaction(&cost_table, GasUnits::new(gas.max_gas_amount()));
+
+        let mut gas_breakdown = GasBreakdown::default();
+        let (result, execution) = metered(&mut cost_strategy, |cost_strategy| {
+            execute(&mut session, cost_strategy)
+        });
+        gas_breakdown.execution = execution;
+
+        let result = result.and_then(|_| {
+            let (charged, storage) = metered(&mut cost_strategy, |cost_strategy| {
+                Self::charge_global_write_gas_usage(cost_strategy, &mut session, &sender)?;
+                self.charge_balance_operations(cost_strategy, &session)
+            });
+            gas_breakdown.storage = storage;
+            charged
+        });
+
+        let mut result = result.and_then(|_| session.finish());
+        if access.is_some() {
+            if let Ok(effects) = &result {
+                Self::record_writes(&state_session, effects);
+            }
+        }
+        if let Some(batch) = writes {
+            result = result.and_then(|mut effects| {
+                self.stage_effects(batch, &mut effects)?;
+                Ok(effects)
+            });
+        }
+
+        let vm_result =
+            self.handle_vm_result(sender, cost_strategy, gas, gas_breakdown, result, dry_run);
+        if let Some(access) = access {
+            *access = state_session.access_set();
+            if vm_result.status_code != StatusCode::EXECUTED {
+                access.writes.clear();
+            }
+        }
+        if let Some(transfers) = transfers {
+            if vm_result.status_code == StatusCode::EXECUTED {
+                *transfers = bank_session.transfer_log();
+            }
+        }
+        vm_result
+    }
+
+    /// Records keys of the resources and modules changed by the transaction.
+    fn record_writes(state_session: &StateSession<'_, State<S, O>>, effects: &TransactionEffects) {
+        for (addr, vals) in &effects.resources {
+            for (struct_tag, _) in vals {
+                state_session.record_write(resource_key(addr, struct_tag));
+            }
+        }
+        for (module_id, _) in &effects.modules {
+            state_session.record_write(module_key(module_id));
+        }
+    }
+
+    /// Moves resource and module changes out of `effects` into the write batch.
+    fn stage_effects(
+        &self,
+        batch: &mut WriteBatch,
+        effects: &mut TransactionEffects,
+    ) -> Result<(), VMError> {
+        let resources = mem::take(&mut effects.resources);
+        let modules = mem::take(&mut effects.modules);
+        if self.toggles.resources {
+            self.stage_resources(batch, resources)?;
+        }
+        if self.toggles.modules {
+            Self::stage_modules(batch, modules);
+        }
+        Ok(())
+    }
+
+    fn run_script(
+        &self,
+        gas: Gas,
+        context: ExecutionContext,
+        tx: ScriptTx,
+        dry_run: bool,
+        writes: Option<&mut WriteBatch>,
+        access: Option<&mut AccessSet>,
+        transfers: Option<&mut Vec<Transfer>>,
+    ) -> VmResult {
+        let (script, args, type_args, senders) = tx.into_inner();
+
+        self.execute_in_session(
+            gas,
+            context,
+            senders.clone(),
+            dry_run,
+            writes,
+            access,
+            transfers,
+            |session, cost_strategy| {
+                self.check_script_opcodes(&script)?;
+                session.execute_script(
+                    script,
+                    type_args,
+                    args,
+                    senders,
+                    cost_strategy,
+                    &self.log_context(),
+                )
+            },
+        )
+    }
+
+    /// Checks the module against the opcode policy.
+    fn check_module_opcodes(&self, module: &[u8]) -> VMResult<()> {
+        if self.opcode_policy.is_allow_all() {
+            return Ok(());
+        }
+        let module =
+            CompiledModule::deserialize(module).map_err(|err| err.finish(Location::Undefined))?;
+        for def in module.function_defs() {
+            if let Some(code) = &def.code {
+                self.check_opcodes(&code.code)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the script against the opcode policy.
+    fn check_script_opcodes(&self, script: &[u8]) -> VMResult<()> {
+        if self.opcode_policy.is_allow_all() {
+            return Ok(());
+        }
+        let script =
+            CompiledScript::deserialize(script).map_err(|err| err.finish(Location::Script))?;
+        self.check_opcodes(&script.as_inner().code.code)
+    }
+
+    fn check_opcodes(&self, code: &[Bytecode]) -> VMResult<()> {
+        match self.opcode_policy.find_forbidden(code) {
+            Some(instruction) => Err(PartialVMError::new(StatusCode::FORBIDDEN_OPCODE)
+                .with_message(format!("Forbidden instruction: {:?}", instruction))
+                .finish(Location::Undefined)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the module against the republish policy.
+    /// Returns `true` if the module replaces the published one.
+    fn check_republish(&self, module: &[u8]) -> VMResult<bool> {
+        let new_module =
+            CompiledModule::deserialize(module).map_err(|err| err.finish(Location::Undefined))?;
+        let id = new_module.self_id();
+        let old_module = match self.state.get_module(&id)? {
+            Some(old_module) => old_module,
+            None => return Ok(false),
+        };
+
+        match self.republish_policy {
+            RepublishPolicy::Reject => {
+                Err(PartialVMError::new(StatusCode::DUPLICATE_MODULE_NAME)
+                    .finish(Location::Module(id)))
+            }
+            RepublishPolicy::AllowAny => Ok(true),
+            RepublishPolicy::AllowCompatible => {
+                let old_module = CompiledModule::deserialize(&old_module)
+                    .map_err(|err| err.finish(Location::Module(id.clone())))?;
+                if is_compatible(&Module::new(&old_module), &Module::new(&new_module)) {
+                    Ok(true)
+                } else {
+                    Err(
+                        PartialVMError::new(StatusCode::BACKWARD_INCOMPATIBLE_MODULE_UPDATE)
+                            .finish(Location::Module(id)),
+                    )
+                }
+            }
+        }
+    }
+
+    /// Evicts the module and the modules depending on it from the loader cache.
+    ///
+    /// Unlike `clear`, other loaded modules stay cached.
+    pub fn clear_module(&self, module_id: &ModuleId) {
+        self.vm.clear_module(module_id);
+    }
+
+    /// Clears the loader cache if a module was replaced, so that the new version is loaded.
+    fn clear_if_republished(&self, republished: bool, result: &VmResult, dry_run: bool) {
+        if republished && !dry_run && result.status_code == StatusCode::EXECUTED {
+            self.clear();
+        }
+    }
+
+    /// Executes the script against the current state without applying its effects.
+    ///
+    /// Returned `VmResult` carries the final status and gas used, so it can be used for gas
+    /// estimation. Events are not passed to the event handler and balances are not changed.
+    pub fn dry_run_script(&self, gas: Gas, context: ExecutionContext, tx: ScriptTx) -> VmResult {
+        self.execute_script(gas, context, tx, true)
+    }
+
+    /// Executes the script and returns the storage writes it would produce without applying them.
+    ///
+    /// Writes are raw storage keys with values, `None` marks a removal. The write set is empty
+    /// if the script fails. Events are not passed to the event handler and balances are not changed.
+    pub fn execute_script_for_writeset(
+        &self,
+        gas: Gas,
+        context: ExecutionContext,
+        tx: ScriptTx,
+    ) -> (VmResult, Vec<(Vec<u8>, Option<Vec<u8>>)>) {
+        let mut batch = WriteBatch::new();
+        let result = self.run_script(gas, context, tx, true, Some(&mut batch), None, None);
+        if result.status_code == StatusCode::EXECUTED {
+            (result, batch.into_writes())
+        } else {
+            (result, vec![])
+        }
+    }
+
+    /// Executes the script and returns the storage keys it read and wrote.
+    ///
+    /// Reads include resources served by the execution context and modules loaded from storage.
+    /// Writes are empty if the script fails.
+    pub fn execute_script_with_access_set(
+        &self,
+        gas: Gas,
+        context: ExecutionContext,
+        tx: ScriptTx,
+        dry_run: bool,
+    ) -> (VmResult, AccessSet) {
+        let mut access = AccessSet::default();
+        let result = self.run_script(gas, context, tx, dry_run, None, Some(&mut access), None);
+        (result, access)
+    }
+
+    /// Executes the script and returns the native balance operations it made.
+    ///
+    /// Operations are in execution order and attributed to the transaction signers. The log is
+    /// empty if the script fails.
+    pub fn execute_script_with_transfer_log(
+        &self,
+        gas: Gas,
+        context: ExecutionContext,
+        tx: ScriptTx,
+        dry_run: bool,
+    ) -> (VmResult, Vec<Transfer>) {
+        let mut transfers = Vec::new();
+        let result = self.run_script(gas, context, tx, dry_run, None, None, Some(&mut transfers));
+        (result, transfers)
+    }
+
+    /// Checks that the module could be published against the current state.
+    ///
+    /// Runs the same checks as `publish_module` (size limit, deserialization, bytecode
+    /// verification, dependency linking and the republish policy) without charging gas or
+    /// writing to storage.
+    pub fn validate_module(&self, module: &[u8]) -> Result<(), VMError> {
+        self.check_module_size(module)?;
+        let compiled =
+            CompiledModule::deserialize(module).map_err(|err| err.finish(Location::Undefined))?;
+        let sender = *compiled.address();
+        self.check_module_opcodes(module)?;
+        let republish = self.check_republish(module)?;
+
+        // A throwaway vm keeps the verified module out of the shared loader cache.
+        let vm = MoveVM::with_max_type_depth(self.max_type_depth);
+        let mut session = vm.new_session(&self.state, &self.bank);
+        let cost_table = self.cost_table.borrow();
+        let mut cost_strategy = CostStrategy::system(&cost_table, GasUnits::new(0));
+        let module = module.to_vec();
+        if republish {
+            session.republish_module(module, sender, &mut cost_strategy, &self.log_context())
+        } else {
+            session.publish_module(module, sender, &mut cost_strategy, &self.log_context())
+        }
+    }
+
+    /// Publishes the module without running the bytecode verifier.
+    ///
+    /// Only for trusted modules known to pass verification, e.g. the standard library at
+    /// genesis: an invalid module published this way is only rejected once it is loaded.
+    /// The module is still linked against its dependencies, checked against the opcode policy
+    /// and charged gas like `publish_module`. Republishing is rejected.
+    #[cfg(feature = "trusted_publish")]
+    pub fn publish_module_unverified(&self, gas: Gas, module: ModuleTx) -> VmResult {
+        let (module, sender) = module.into_inner();
+        let cost_table = self.cost_table.borrow();
+        if let Err(rejected) = Self::check_gas(&cost_table, &gas) {
+            return rejected;
+        }
+        let mut cost_strategy =
+            CostStrategy::transaction(&cost_table, GasUnits::new(gas.max_gas_amount()));
+        let mut session = self.vm.new_session(&self.state, &self.bank);
+
+        let mut gas_breakdown = GasBreakdown::default();
+        let result = self
+            .check_module_size(&module)
+            .and_then(|_| {
+                let (charged, intrinsic) = metered(&mut cost_strategy, |cost_strategy| {
+                    cost_strategy.charge_intrinsic_gas(AbstractMemorySize::new(module.len() as u64))
+                });
+                gas_breakdown.intrinsic = intrinsic;
+                charged
+            })
+            .and_then(|_| self.check_module_opcodes(&module))
+            .and_then(|_| {
+                let (result, execution) = metered(&mut cost_strategy, |cost_strategy| {
+                    session.publish_module_unverified(
+                        module,
+                        sender,
+                        cost_strategy,
+                        &self.log_context(),
+                    )
+                });
+                gas_breakdown.execution = execution;
+                result
+            })
+            .and_then(|_| {
+                let (charged, storage) = metered(&mut cost_strategy, |cost_strategy| {
+                    Self::charge_global_write_gas_usage(cost_strategy, &mut session, &sender)
+                });
+                gas_breakdown.storage = storage;
+                charged
+            })
+            .and_then(|_| session.finish());
+
+        self.handle_vm_result(sender, cost_strategy, gas, gas_breakdown, result, false)
+    }
+
+    /// Returns gas units needed to publish the module, without publishing it.
+    ///
+    /// Charges the same intrinsic, verification and storage gas as `publish_module` against the
+    /// largest budget that does not overflow internal gas units. Effects are discarded and the
+    /// module is kept out of the loader cache.
+    pub fn estimate_module_gas(&self, module: &[u8]) -> Result<u64, VMError> {
+        self.check_module_size(module)?;
+        let sender = *CompiledModule::deserialize(module)
+            .map_err(|err| err.finish(Location::Undefined))?
+            .address();
+
+        let vm = MoveVM::with_max_type_depth(self.max_type_depth);
+        let mut session = vm.new_session(&self.state, &self.bank);
+        let cost_table = self.cost_table.borrow();
+        let mut cost_strategy =
+            CostStrategy::transaction(&cost_table, GasUnits::new(GAS_AMOUNT_MAX_VALUE));
         cost_strategy.charge_intrinsic_gas(AbstractMemorySize::new(module.len() as u64))?;
+        self._publish_module(
+            &mut session,
+            module.to_vec(),
+            sender,
+            &mut cost_strategy,
+            &mut GasBreakdown::default(),
+        )?;
+        Ok(GAS_AMOUNT_MAX_VALUE - cost_strategy.remaining_gas().get())
+    }
+
+    /// Checks the module against the module size limit.
+    fn check_module_size(&self, module: &[u8]) -> VMResult<()> {
+        if module.len() as u64 > self.max_module_bytes {
+            Err(PartialVMError::new(StatusCode::EXCEEDED_MAX_MODULE_SIZE)
+                .with_message(format!(
+                    "Module size {} exceeds the limit of {} bytes",
+                    module.len(),
+                    self.max_module_bytes
+                ))
+                .finish(Location::Undefined))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Publishes the standard library modules that are not published yet and pins them in the
+    /// loader cache, so that `Vm::clear` loads them again right away.
+    ///
+    /// Modules must be given in dependency order. No gas is charged, but the modules go through
+    /// the same size, opcode and republish checks as `publish_module` and are committed like
+    /// transaction effects.
+    pub fn preload_stdlib(&self, modules: &[ModuleTx]) -> Result<(), VMError> {
+        let mut ids = Vec::with_capacity(modules.len());
+        {
+            let mut session = self.vm.new_session(&self.state, &self.bank);
+            let cost_table = self.cost_table.borrow();
+            let mut cost_strategy = CostStrategy::system(&cost_table, GasUnits::new(0));
+            for module in modules {
+                self.check_module_size(module.code())?;
+                let id = CompiledModule::deserialize(module.code())
+                    .map_err(|err| err.finish(Location::Undefined))?
+                    .self_id();
+                if self.state.get_module(&id)?.is_none() {
+                    self._publish_module(
+                        &mut session,
+                        module.code().to_vec(),
+                        *module.sender(),
+                        &mut cost_strategy,
+                        &mut GasBreakdown::default(),
+                    )?;
+                }
+                ids.push(id);
+            }
+            let mut effects = session.finish()?;
+            let mut batch = WriteBatch::new();
+            self.stage_effects(&mut batch, &mut effects)?;
+            batch.commit(&self.state);
+        }
 
-        let result = session.publish_module(module, sender, cost_strategy, &NoContextLog::new());
-        Self::charge_global_write_gas_usage(cost_strategy, session, &sender)?;
+        let mut session = self.vm.new_session(&self.state, &self.bank);
+        for id in &ids {
+            session.pin_module(id, &self.log_context())?;
+        }
+        Ok(())
+    }
+
+    /// Publishes modules in the given order.
+    ///
+    /// Every dependency of a module must be either already published or precede it in `modules`,
+    /// otherwise the bundle is rejected with `MISSING_DEPENDENCY`.
+    pub fn publish_module_bundle_ordered(&self, gas: Gas, modules: Vec<ModuleTx>) -> VmResult {
+        let sender = modules
+            .first()
+            .map(|module| *module.sender())
+            .unwrap_or(NONE_ADDRESS);
+        let cost_table = self.cost_table.borrow();
+        if let Err(rejected) = Self::check_gas(&cost_table, &gas) {
+            return rejected;
+        }
+        let mut cost_strategy =
+            CostStrategy::transaction(&cost_table, GasUnits::new(gas.max_gas_amount()));
+
+        // Reject oversized modules before doing any work on them.
+        if let Err(err) = modules
+            .iter()
+            .try_for_each(|module| self.check_module_size(module.code()))
+        {
+            return self.handle_vm_result(
+                sender,
+                cost_strategy,
+                gas,
+                GasBreakdown::default(),
+                Err(err),
+                false,
+            );
+        }
+
+        // A fresh vm is used for the same reasons as in `publish_module_package`.
+        let vm = MoveVM::with_max_type_depth(self.max_type_depth);
+        let mut session = vm.new_session(&self.state, &self.bank);
+
+        let mut gas_breakdown = GasBreakdown::default();
+        let total_len = modules
+            .iter()
+            .map(|module| module.code().len() as u64)
+            .sum();
+        let (charged, intrinsic) = metered(&mut cost_strategy, |cost_strategy| {
+            cost_strategy.charge_intrinsic_gas(AbstractMemorySize::new(total_len))
+        });
+        gas_breakdown.intrinsic = intrinsic;
+        if let Err(err) = charged {
+            return self.handle_vm_result(
+                sender,
+                cost_strategy,
+                gas,
+                gas_breakdown,
+                Err(err),
+                false,
+            );
+        }
+
+        let mut published = HashSet::new();
+        let mut republished = false;
+        for module in modules {
+            let (module, sender) = module.into_inner();
+            let result = self
+                .check_dependencies_published(&module, &published)
+                .and_then(|id| {
+                    published.insert(id);
+                    self._publish_module(
+                        &mut session,
+                        module,
+                        sender,
+                        &mut cost_strategy,
+                        &mut gas_breakdown,
+                    )
+                });
+            match result {
+                Ok(replaced) => republished |= replaced,
+                Err(err) => {
+                    return self.handle_vm_result(
+                        sender,
+                        cost_strategy,
+                        gas,
+                        gas_breakdown,
+                        Err(err),
+                        false,
+                    )
+                }
+            }
+        }
+        let result = self.handle_vm_result(
+            sender,
+            cost_strategy,
+            gas,
+            gas_breakdown,
+            session.finish(),
+            false,
+        );
+        self.clear_if_republished(republished, &result, false);
         result
     }
 
+    /// Checks that all dependencies of the module are either stored or in `published`.
+    /// Returns the module identifier.
+    fn check_dependencies_published(
+        &self,
+        module: &[u8],
+        published: &HashSet<ModuleId>,
+    ) -> VMResult<ModuleId> {
+        let module =
+            CompiledModule::deserialize(module).map_err(|err| err.finish(Location::Undefined))?;
+        let id = module.self_id();
+        for dep in module_dependencies(&module) {
+            if !published.contains(&dep) && self.state.get_module(&dep)?.is_none() {
+                return Err(PartialVMError::new(StatusCode::MISSING_DEPENDENCY)
+                    .with_message(format!(
+                        "Module {} depends on {} which is not published before it.",
+                        id, dep
+                    ))
+                    .finish(Location::Module(id)));
+            }
+        }
+        Ok(id)
+    }
+
+    /// Charges intrinsic gas once for the summed size of the package modules.
+    fn charge_package_intrinsic_gas(
+        cost_strategy: &mut CostStrategy,
+        modules: &[Vec<u8>],
+    ) -> VMResult<()> {
+        let total_len = modules.iter().map(|module| module.len() as u64).sum();
+        cost_strategy.charge_intrinsic_gas(AbstractMemorySize::new(total_len))
+    }
+
+    /// Charges the configured per-byte gas for the serialized messages of the events.
+    fn charge_event_gas(
+        &self,
+        cost_strategy: &mut CostStrategy,
+        gas_breakdown: &mut GasBreakdown,
+        events: &[Event],
+    ) -> VMResult<()> {
+        let total_len = events
+            .iter()
+            .map(|(_, _, msg, _)| msg.len() as u64)
+            .sum::<u64>();
+        let (charged, event_gas) = metered(cost_strategy, |cost_strategy| {
+            let total_cost = total_len
+                .saturating_mul(self.event_byte_gas)
+                .saturating_mul(
+                    cost_strategy
+                        .cost_table()
+                        .gas_constants
+                        .gas_unit_scaling_factor,
+                );
+            cost_strategy
+                .deduct_gas(GasUnits::new(total_cost))
+                .map_err(|p_err| p_err.finish(Location::Undefined))
+        });
+        gas_breakdown.events += event_gas;
+        charged
+    }
+
+    /// Charges the bank's per-operation gas for every balance operation made in the session.
+    fn charge_balance_operations<R, NB>(
+        &self,
+        cost_strategy: &mut CostStrategy,
+        session: &Session<'_, '_, R, NB>,
+    ) -> VMResult<()>
+    where
+        R: RemoteCache,
+        NB: NativeBalance,
+    {
+        let total_cost = session
+            .num_wallet_operations()
+            .saturating_mul(self.bank.config().operation_gas)
+            .saturating_mul(
+                cost_strategy
+                    .cost_table()
+                    .gas_constants
+                    .gas_unit_scaling_factor,
+            );
+        cost_strategy
+            .deduct_gas(GasUnits::new(total_cost))
+            .map_err(|p_err| p_err.finish(Location::Undefined))
+    }
+
     fn charge_global_write_gas_usage<R, NB>(
         cost_strategy: &mut CostStrategy,
         session: &mut Session<'_, '_, R, NB>,
@@ -216,6 +1256,224 @@ where
     }
 }
 
+impl<S, E, O, B> Mvm<S, E, O, B>
+where
+    S: StorageIter,
+    E: EventHandler,
+    O: Oracle,
+    B: BalanceAccess,
+{
+    /// Removes the published module from the state and evicts it from the loader cache.
+    ///
+    /// Only the owner of the module address can remove it. The module is kept if another
+    /// published module depends on it or a resource of its types is stored, in which case
+    /// `MODULE_IN_USE` is returned. Every byte read while looking for such modules and resources
+    /// is charged as execution gas.
+    pub fn remove_module(&self, gas: Gas, module_id: ModuleId, sender: AccountAddress) -> VmResult {
+        let cost_table = self.cost_table.borrow();
+        if let Err(rejected) = Self::check_gas(&cost_table, &gas) {
+            return rejected;
+        }
+        let mut cost_strategy =
+            CostStrategy::transaction(&cost_table, GasUnits::new(gas.max_gas_amount()));
+
+        let mut gas_breakdown = GasBreakdown::default();
+        let result = self
+            .removed_module_size(&module_id, &sender)
+            .and_then(|size| {
+                let (charged, intrinsic) = metered(&mut cost_strategy, |cost_strategy| {
+                    cost_strategy.charge_intrinsic_gas(AbstractMemorySize::new(size))
+                });
+                gas_breakdown.intrinsic = intrinsic;
+                charged
+            })
+            .and_then(|_| {
+                let (unused, execution) = metered(&mut cost_strategy, |cost_strategy| {
+                    self.check_module_unused(&module_id, cost_strategy)
+                });
+                gas_breakdown.execution = execution;
+                unused
+            })
+            .map(|_| TransactionEffects {
+                resources: vec![],
+                modules: vec![],
+                events: vec![],
+                wallet_ops: HashMap::new(),
+            });
+        let result =
+            self.handle_vm_result(sender, cost_strategy, gas, gas_breakdown, result, false);
+        if result.status_code == StatusCode::EXECUTED {
+            let mut batch = WriteBatch::new();
+            batch.delete(AccessKey::from(&module_id));
+            batch.commit(&self.state);
+            self.vm.clear_module(&module_id);
+        }
+        result
+    }
+
+    /// Checks that the sender owns the published module and returns the size of its blob.
+    fn removed_module_size(&self, module_id: &ModuleId, sender: &AccountAddress) -> VMResult<u64> {
+        if module_id.address() != sender {
+            return Err(
+                PartialVMError::new(StatusCode::MODULE_ADDRESS_DOES_NOT_MATCH_SENDER)
+                    .finish(Location::Module(module_id.clone())),
+            );
+        }
+        match self.state.get_module(module_id)? {
+            Some(blob) => Ok(blob.len() as u64),
+            None => Err(PartialVMError::new(StatusCode::LINKER_ERROR)
+                .with_message(format!("Module {} is not published.", module_id))
+                .finish(Location::Module(module_id.clone()))),
+        }
+    }
+
+    /// Checks that no published module depends on the module and no stored resource refers to
+    /// its types.
+    ///
+    /// The scan stops with `OUT_OF_GAS` once the scanned keys and module blobs exceed the budget.
+    fn check_module_unused(
+        &self,
+        module_id: &ModuleId,
+        cost_strategy: &mut CostStrategy,
+    ) -> VMResult<()> {
+        let location = || Location::Module(module_id.clone());
+        for (key, blob) in self.state.store().scan_prefix(&[CODE_TAG]) {
+            charge_scanned_bytes(cost_strategy, key.len() + blob.len())?;
+            let id = match AccessPath::decode_key(&key)
+                .ok()
+                .and_then(|path| path.module_id())
+            {
+                Some(id) if &id != module_id => id,
+                _ => continue,
+            };
+            let module = CompiledModule::deserialize(&blob)
+                .map_err(|err| err.finish(Location::Module(id.clone())))?;
+            if module_dependencies(&module).contains(module_id) {
+                return Err(PartialVMError::new(StatusCode::MODULE_IN_USE)
+                    .with_message(format!("Module {} depends on {}.", id, module_id))
+                    .finish(location()));
+            }
+        }
+
+        // Resource keys start with the holder address, so resources of the module types can't be
+        // found by prefix. Only the keys are scanned, and only resource keys whose type encodes
+        // the module id are decoded.
+        let encoded_id = bcs::to_bytes(module_id).map_err(|err| {
+            PartialVMError::new(StatusCode::VALUE_SERIALIZATION_ERROR)
+                .with_message(err.to_string())
+                .finish(location())
+        })?;
+        for key in self.state.store().scan_keys(&[]) {
+            charge_scanned_bytes(cost_strategy, key.len())?;
+            if !may_hold_module_type(&key, &encoded_id) {
+                continue;
+            }
+            let tag = match AccessPath::decode_key(&key)
+                .ok()
+                .and_then(|path| path.resource_tag())
+            {
+                Some(tag) => tag,
+                None => continue,
+            };
+            if struct_refers_to(&tag, module_id) {
+                return Err(PartialVMError::new(StatusCode::MODULE_IN_USE)
+                    .with_message(format!("Resource {} is published.", tag))
+                    .finish(location()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Charges the global memory read cost for bytes loaded while scanning the storage.
+fn charge_scanned_bytes(cost_strategy: &mut CostStrategy, bytes: usize) -> VMResult<()> {
+    let cost = cost_strategy
+        .cost_table()
+        .gas_constants
+        .global_memory_per_byte_cost
+        .mul(AbstractMemorySize::new(bytes as u64));
+    cost_strategy
+        .deduct_gas(cost)
+        .map_err(|p_err| p_err.finish(Location::Undefined))
+}
+
+/// Returns `true` if the storage key is a resource key whose type contains the bcs encoded
+/// module id, i.e. if the resource may refer to a type declared in the module.
+fn may_hold_module_type(key: &[u8], encoded_id: &[u8]) -> bool {
+    match key.get(AccountAddress::LENGTH..) {
+        Some([tag, type_bytes @ ..]) if *tag == AccessPath::RESOURCE_TAG => type_bytes
+            .windows(encoded_id.len())
+            .any(|window| window == encoded_id),
+        _ => false,
+    }
+}
+
+/// Returns `true` if the struct or any of its type parameters is declared in the module.
+fn struct_refers_to(tag: &StructTag, module_id: &ModuleId) -> bool {
+    (&tag.address == module_id.address() && tag.module.as_ident_str() == module_id.name())
+        || tag
+            .type_params
+            .iter()
+            .any(|param| type_refers_to(param, module_id))
+}
+
+/// Returns `true` if the type refers to a struct declared in the module, at any vector depth.
+fn type_refers_to(tag: &TypeTag, module_id: &ModuleId) -> bool {
+    match tag {
+        TypeTag::Struct(tag) => struct_refers_to(tag, module_id),
+        TypeTag::Vector(inner) => type_refers_to(inner, module_id),
+        _ => false,
+    }
+}
+
+/// Move VM over in-memory state.
+pub type InMemoryMvm = Mvm<InMemoryStorage, InMemoryEventHandler, InMemoryOracle, InMemoryBank>;
+
+impl InMemoryMvm {
+    /// Creates a new move vm over empty in-memory state with the default configuration.
+    ///
+    /// ```
+    /// use move_core_types::identifier::Identifier;
+    /// use move_core_types::language_storage::{ModuleId, CORE_CODE_ADDRESS};
+    /// use mvm::data::{AccessKey, Storage};
+    /// use mvm::mvm::InMemoryMvm;
+    /// use mvm::types::{Gas, ModuleTx};
+    /// use mvm::Vm;
+    ///
+    /// let vm = InMemoryMvm::empty();
+    /// let code = include_bytes!("../tests/assets/target/modules/Store.mv").to_vec();
+    /// let res = vm.publish_module(
+    ///     Gas::new(10_000, 1).unwrap(),
+    ///     ModuleTx::new(code.clone(), CORE_CODE_ADDRESS),
+    ///     false,
+    /// );
+    /// assert_eq!(res.status_code, move_core_types::vm_status::StatusCode::EXECUTED);
+    ///
+    /// let id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    /// assert_eq!(vm.store().get(AccessKey::from(&id).as_ref()), Some(code));
+    /// ```
+    pub fn empty() -> InMemoryMvm {
+        Mvm::from_config(
+            InMemoryStorage::default(),
+            InMemoryEventHandler::default(),
+            InMemoryOracle::default(),
+            InMemoryBank::default(),
+            VmConfig::default(),
+        )
+    }
+}
+
+/// Runs `f` and returns its result along with the gas it consumed.
+fn metered<T>(
+    cost_strategy: &mut CostStrategy,
+    f: impl FnOnce(&mut CostStrategy) -> T,
+) -> (T, u64) {
+    let before = cost_strategy.remaining_gas().get();
+    let result = f(cost_strategy);
+    let consumed = before.saturating_sub(cost_strategy.remaining_gas().get());
+    (result, consumed)
+}
+
 impl<S, E, O, B> Vm for Mvm<S, E, O, B>
 where
     S: Storage,
@@ -225,15 +1483,53 @@ where
 {
     fn publish_module(&self, gas: Gas, module: ModuleTx, dry_run: bool) -> VmResult {
         let (module, sender) = module.into_inner();
+        let cost_table = self.cost_table.borrow();
+        if let Err(rejected) = Self::check_gas(&cost_table, &gas) {
+            return rejected;
+        }
         let mut cost_strategy =
-            CostStrategy::transaction(&self.cost_table, GasUnits::new(gas.max_gas_amount()));
+            CostStrategy::transaction(&cost_table, GasUnits::new(gas.max_gas_amount()));
+
+        // Reject oversized modules before doing any work on them.
+        if let Err(err) = self.check_module_size(&module) {
+            return self.handle_vm_result(
+                sender,
+                cost_strategy,
+                gas,
+                GasBreakdown::default(),
+                Err(err),
+                dry_run,
+            );
+        }
+
         let mut session = self.vm.new_session(&self.state, &self.bank);
 
-        let result = self
-            ._publish_module(&mut session, module, sender, &mut cost_strategy)
-            .and_then(|_| session.finish());
+        let mut gas_breakdown = GasBreakdown::default();
+        let (charged, intrinsic) = metered(&mut cost_strategy, |cost_strategy| {
+            cost_strategy.charge_intrinsic_gas(AbstractMemorySize::new(module.len() as u64))
+        });
+        gas_breakdown.intrinsic = intrinsic;
+
+        let mut republished = false;
+        let result = charged
+            .and_then(|_| {
+                self._publish_module(
+                    &mut session,
+                    module,
+                    sender,
+                    &mut cost_strategy,
+                    &mut gas_breakdown,
+                )
+            })
+            .and_then(|replaced| {
+                republished = replaced;
+                session.finish()
+            });
 
-        self.handle_vm_result(sender, cost_strategy, gas, result, dry_run)
+        let result =
+            self.handle_vm_result(sender, cost_strategy, gas, gas_breakdown, result, dry_run);
+        self.clear_if_republished(republished, &result, dry_run);
+        result
     }
 
     fn publish_module_package(
@@ -243,22 +1539,86 @@ where
         dry_run: bool,
     ) -> VmResult {
         let (modules, sender) = package.into_inner();
+        let cost_table = self.cost_table.borrow();
+        if let Err(rejected) = Self::check_gas(&cost_table, &gas) {
+            return rejected;
+        }
         let mut cost_strategy =
-            CostStrategy::transaction(&self.cost_table, GasUnits::new(gas.max_gas_amount()));
+            CostStrategy::transaction(&cost_table, GasUnits::new(gas.max_gas_amount()));
+
+        // Reject oversized modules before doing any work on them.
+        if let Err(err) = modules
+            .iter()
+            .try_for_each(|module| self.check_module_size(module))
+        {
+            return self.handle_vm_result(
+                sender,
+                cost_strategy,
+                gas,
+                GasBreakdown::default(),
+                Err(err),
+                dry_run,
+            );
+        }
 
         // We need to create a new vm to publish module packages.
         // Because during batch publishing, the cache mutates.
         // This is not the correct behavior for the dry_run case or for rolling back a transaction.
-        let vm = MoveVM::new();
+        let vm = MoveVM::with_max_type_depth(self.max_type_depth);
         let mut session = vm.new_session(&self.state, &self.bank);
 
+        let mut gas_breakdown = GasBreakdown::default();
+        let (charged, intrinsic) = metered(&mut cost_strategy, |cost_strategy| {
+            Self::charge_package_intrinsic_gas(cost_strategy, &modules)
+        });
+        gas_breakdown.intrinsic = intrinsic;
+
+        let modules = match charged.and_then(|_| sort_by_dependencies(modules)) {
+            Ok(modules) => modules,
+            Err(err) => {
+                return self.handle_vm_result(
+                    sender,
+                    cost_strategy,
+                    gas,
+                    gas_breakdown,
+                    Err(err),
+                    dry_run,
+                )
+            }
+        };
+
+        let mut republished = false;
         for module in modules {
-            if let Err(err) = self._publish_module(&mut session, module, sender, &mut cost_strategy)
-            {
-                return self.handle_vm_result(sender, cost_strategy, gas, Err(err), dry_run);
+            match self._publish_module(
+                &mut session,
+                module,
+                sender,
+                &mut cost_strategy,
+                &mut gas_breakdown,
+            ) {
+                Ok(replaced) => republished |= replaced,
+                Err(err) => {
+                    return self.handle_vm_result(
+                        sender,
+                        cost_strategy,
+                        gas,
+                        gas_breakdown,
+                        Err(err),
+                        dry_run,
+                    )
+                }
             }
         }
-        self.handle_vm_result(sender, cost_strategy, gas, session.finish(), dry_run)
+        let result = self.handle_vm_result(
+            sender,
+            cost_strategy,
+            gas,
+            gas_breakdown,
+            session.finish(),
+            dry_run,
+        );
+        self.clear_if_republished(republished, &result, dry_run);
+        result
     }
 
     fn execute_script(
@@ -268,38 +1628,52 @@ where
         tx: ScriptTx,
         dry_run: bool,
     ) -> VmResult {
-        let state_session = StateSession::new(&self.state, context);
-        let mut session = self.vm.new_session(&state_session, &self.bank);
+        self.run_script(gas, context, tx, dry_run, None, None, None)
+    }
 
-        let (script, args, type_args, senders) = tx.into_inner();
+    fn execute_function(
+        &self,
+        gas: Gas,
+        context: ExecutionContext,
+        tx: FunctionTx,
+        dry_run: bool,
+    ) -> VmResult {
+        let (module, function, args, type_args, senders) = tx.into_inner();
         let sender = senders.get(0).cloned().unwrap_or(NONE_ADDRESS);
 
-        let mut cost_strategy =
-            CostStrategy::transaction(&self.cost_table, GasUnits::new(gas.max_gas_amount()));
-
-        let result = session
-            .execute_script(
-                script,
-                type_args,
-                args,
-                senders,
-                &mut cost_strategy,
-                &NoContextLog::new(),
-            )
-            .and_then(|_| {
-                Self::charge_global_write_gas_usage(&mut cost_strategy, &mut session, &sender)
-            });
-
-        self.handle_vm_result(
-            sender,
-            cost_strategy,
+        self.execute_in_session(
             gas,
-            result.and_then(|_| session.finish()),
+            context,
+            senders.clone(),
             dry_run,
+            None,
+            None,
+            None,
+            |session, cost_strategy| {
+                let mut signers_and_args = senders
+                    .into_iter()
+                    .map(Value::transaction_argument_signer_reference)
+                    .collect::<Vec<_>>();
+                signers_and_args.extend(args);
+                session.execute_function(
+                    &module,
+                    &function,
+                    type_args,
+                    signers_and_args,
+                    sender,
+                    cost_strategy,
+                    &self.log_context(),
+                )
+            },
         )
     }
 
     fn clear(&self) {
         self.vm.clear();
+        // Pinned modules are loaded again right away, so they stay cached across `clear`.
+        let mut session = self.vm.new_session(&self.state, &self.bank);
+        if let Err(err) = session.load_pinned_modules(&self.log_context()) {
+            log::warn!("Failed to load pinned modules: {:?}", err);
+        }
     }
 }
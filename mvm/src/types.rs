@@ -1,10 +1,14 @@
+use crate::vm_config::VmConfig;
+use alloc::string::String;
 use alloc::vec::Vec;
 use anyhow::*;
 use core::convert::TryFrom;
 use core::fmt;
 use move_core_types::account_address::AccountAddress;
+use move_core_types::gas_schedule::{GasAlgebra, GasConstants};
 use move_core_types::identifier::Identifier;
-use move_core_types::language_storage::{StructTag, TypeTag};
+use move_core_types::language_storage::{ModuleId, StructTag, TypeTag};
+use move_core_types::value::MoveValue;
 use move_core_types::vm_status::StatusCode;
 use move_lang::parser::ast::{ModuleAccess_, ModuleIdent_, Type, Type_};
 use move_lang::parser::lexer::{Lexer, Tok};
@@ -12,11 +16,19 @@ use move_lang::parser::syntax::parse_type;
 use move_vm_types::values::Value;
 use parity_scale_codec::{Decode, Encode};
 use serde::{Deserialize, Serialize};
+use vm::access::ScriptAccess;
+use vm::errors::{Location, VMError};
+use vm::file_format::{
+    Bytecode, CodeOffset, CompiledScript, FunctionDefinitionIndex, SignatureToken,
+};
+use vm::file_format_common::{instruction_key, Opcodes};
 
-const GAS_AMOUNT_MAX_VALUE: u64 = u64::MAX / 1000;
+/// Largest gas amount which does not overflow when scaled to internal gas units.
+pub(crate) const GAS_AMOUNT_MAX_VALUE: u64 = u64::MAX / 1000;
 
 /// Stores gas metadata for vm execution.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde-types", derive(Serialize, Deserialize))]
 pub struct Gas {
     /// Max gas units to be used in transaction execution.
     pub(crate) max_gas_amount: u64,
@@ -26,17 +38,57 @@ pub struct Gas {
 
 impl Gas {
     /// Constructor.
-    pub fn new(max_gas_amount: u64, gas_unit_price: u64) -> Result<Gas> {
-        ensure!(
-            max_gas_amount < GAS_AMOUNT_MAX_VALUE,
-            "max_gas_amount value must be in the range from 0 to {}",
-            GAS_AMOUNT_MAX_VALUE
-        );
+    ///
+    /// Validates the gas against the default vm configuration, see [`Gas::with_config`]. The vm
+    /// checks the gas against its own cost table again before using it.
+    pub fn new(max_gas_amount: u64, gas_unit_price: u64) -> Result<Gas, GasError> {
+        Gas::validate(max_gas_amount, gas_unit_price, &GasConstants::default())
+    }
 
-        Ok(Gas {
+    /// Creates gas metadata checked against the bounds of the given vm configuration.
+    ///
+    /// Returns an error if `gas_unit_price` is zero or outside of the configured price range, or
+    /// if `max_gas_amount` exceeds the configured maximum number of gas units.
+    pub fn with_config(
+        max_gas_amount: u64,
+        gas_unit_price: u64,
+        config: &VmConfig,
+    ) -> Result<Gas, GasError> {
+        Gas::validate(
             max_gas_amount,
             gas_unit_price,
-        })
+            &config.gas_schedule.gas_constants,
+        )
+    }
+
+    /// Creates gas metadata without any validation.
+    ///
+    /// The caller must make sure that `max_gas_amount` does not overflow when scaled to internal gas units.
+    pub(crate) fn new_unchecked(max_gas_amount: u64, gas_unit_price: u64) -> Gas {
+        Gas {
+            max_gas_amount,
+            gas_unit_price,
+        }
+    }
+
+    /// Creates gas metadata from the max fee the sender is willing to pay.
+    ///
+    /// The max gas amount is `max_fee / gas_unit_price`, the remainder is not spent.
+    /// Fees covering more gas than the vm allows are clamped to the max gas amount limit.
+    pub fn from_fee(max_fee: u128, gas_unit_price: u64) -> Result<Gas, GasError> {
+        if gas_unit_price == 0 {
+            return Err(GasError::ZeroUnitPrice);
+        }
+        let limit = Gas::max_gas_amount_limit(&GasConstants::default());
+        let max_gas_amount = u64::try_from(max_fee / gas_unit_price as u128)
+            .unwrap_or(u64::MAX)
+            .min(limit);
+        Gas::new(max_gas_amount, gas_unit_price)
+    }
+
+    /// Checks the gas against the bounds of the given gas constants.
+    pub(crate) fn check(&self, constants: &GasConstants) -> Result<(), GasError> {
+        Gas::validate(self.max_gas_amount, self.gas_unit_price, constants).map(|_| ())
     }
 
     /// Returns max gas units to be used in transaction execution.
@@ -48,8 +100,94 @@ impl Gas {
     pub fn gas_unit_price(&self) -> u64 {
         self.gas_unit_price
     }
+
+    /// Returns the largest max gas amount allowed by the gas constants.
+    fn max_gas_amount_limit(constants: &GasConstants) -> u64 {
+        // Gas is scaled to internal units by the vm, so the limit must not overflow after scaling.
+        let scaled_limit = GAS_AMOUNT_MAX_VALUE
+            .min(u64::MAX / constants.gas_unit_scaling_factor.max(1))
+            .saturating_sub(1);
+        constants
+            .maximum_number_of_gas_units
+            .get()
+            .min(scaled_limit)
+    }
+
+    fn validate(
+        max_gas_amount: u64,
+        gas_unit_price: u64,
+        constants: &GasConstants,
+    ) -> Result<Gas, GasError> {
+        if gas_unit_price == 0 {
+            return Err(GasError::ZeroUnitPrice);
+        }
+        let min_price = constants.min_price_per_gas_unit.get();
+        let max_price = constants.max_price_per_gas_unit.get();
+        if gas_unit_price < min_price || gas_unit_price > max_price {
+            return Err(GasError::UnitPriceOutOfRange {
+                price: gas_unit_price,
+                min: min_price,
+                max: max_price,
+            });
+        }
+        let limit = Gas::max_gas_amount_limit(constants);
+        if max_gas_amount > limit {
+            return Err(GasError::MaxGasAmountTooLarge {
+                amount: max_gas_amount,
+                limit,
+            });
+        }
+
+        Ok(Gas::new_unchecked(max_gas_amount, gas_unit_price))
+    }
 }
 
+/// Reason why gas metadata was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasError {
+    /// The gas unit price is zero.
+    ZeroUnitPrice,
+    /// The gas unit price is outside of the range allowed by the vm configuration.
+    UnitPriceOutOfRange { price: u64, min: u64, max: u64 },
+    /// The max gas amount exceeds the limit allowed by the vm configuration.
+    MaxGasAmountTooLarge { amount: u64, limit: u64 },
+}
+
+impl GasError {
+    /// Returns the vm status code reporting the error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            GasError::ZeroUnitPrice => StatusCode::GAS_UNIT_PRICE_BELOW_MIN_BOUND,
+            GasError::UnitPriceOutOfRange { price, min, .. } if price < min => {
+                StatusCode::GAS_UNIT_PRICE_BELOW_MIN_BOUND
+            }
+            GasError::UnitPriceOutOfRange { .. } => StatusCode::GAS_UNIT_PRICE_ABOVE_MAX_BOUND,
+            GasError::MaxGasAmountTooLarge { .. } => {
+                StatusCode::MAX_GAS_UNITS_EXCEEDS_MAX_GAS_UNITS_BOUND
+            }
+        }
+    }
+}
+
+impl fmt::Display for GasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GasError::ZeroUnitPrice => write!(f, "gas_unit_price must be greater than 0"),
+            GasError::UnitPriceOutOfRange { price, min, max } => write!(
+                f,
+                "gas_unit_price {} must be in range [{}, {}]",
+                price, min, max
+            ),
+            GasError::MaxGasAmountTooLarge { amount, limit } => {
+                write!(f, "max_gas_amount {} must not exceed {}", amount, limit)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GasError {}
+
 /// Module transaction.
 #[derive(Clone, Encode, Decode)]
 pub struct ModuleTx {
@@ -68,6 +206,11 @@ impl ModuleTx {
         &self.code
     }
 
+    /// Returns module sender.
+    pub fn sender(&self) -> &AccountAddress {
+        &self.sender
+    }
+
     /// Convert into internal data.
     pub fn into_inner(self) -> (Vec<u8>, AccountAddress) {
         (self.code, self.sender)
@@ -108,6 +251,14 @@ impl ScriptTx {
         }
     }
 
+    /// Constructor of a system transaction.
+    ///
+    /// System (genesis) scripts are executed without signers, so `script` must not take any
+    /// `&signer` parameters.
+    pub fn system(script: Vec<u8>, args: Vec<ScriptArg>, type_args: Vec<TypeTag>) -> Self {
+        ScriptTx::new(script, args, type_args, vec![])
+    }
+
     /// Script bytecode.
     pub fn code(&self) -> &[u8] {
         &self.code
@@ -140,8 +291,279 @@ impl fmt::Debug for ScriptTx {
     }
 }
 
+/// Builds a `ScriptTx`, checking the arguments against the script signature.
+pub struct ScriptTxBuilder {
+    code: Vec<u8>,
+    signers: usize,
+    type_parameters: usize,
+    parameters: Vec<SignatureToken>,
+    args: Vec<MoveValue>,
+    type_args: Vec<TypeTag>,
+    senders: Vec<AccountAddress>,
+}
+
+impl ScriptTxBuilder {
+    /// Creates a builder for the given script bytecode.
+    pub fn new(code: Vec<u8>) -> Result<ScriptTxBuilder> {
+        let script = CompiledScript::deserialize(&code)
+            .map_err(|err| anyhow!("Failed to deserialize script: {:?}", err))?;
+        let parameters = &script.signature_at(script.as_inner().parameters).0;
+        let signers = parameters
+            .iter()
+            .take_while(|param| match param {
+                SignatureToken::Reference(inner) => **inner == SignatureToken::Signer,
+                _ => false,
+            })
+            .count();
+
+        Ok(ScriptTxBuilder {
+            signers,
+            type_parameters: script.as_inner().type_parameters.len(),
+            parameters: parameters[signers..].to_vec(),
+            code,
+            args: vec![],
+            type_args: vec![],
+            senders: vec![],
+        })
+    }
+
+    /// Adds an argument of the main function.
+    pub fn arg(mut self, arg: MoveValue) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// Adds a type parameter of the main function.
+    pub fn type_arg(mut self, type_arg: TypeTag) -> Self {
+        self.type_args.push(type_arg);
+        self
+    }
+
+    /// Adds a transaction sender.
+    pub fn sender(mut self, sender: AccountAddress) -> Self {
+        self.senders.push(sender);
+        self
+    }
+
+    /// Type-checks the arguments and builds the transaction.
+    pub fn build(self) -> Result<ScriptTx> {
+        ensure!(
+            self.signers == self.senders.len(),
+            "Script expects {} signers, got {}",
+            self.signers,
+            self.senders.len()
+        );
+        ensure!(
+            self.type_args.len() == self.type_parameters,
+            "Script expects {} type arguments, got {}",
+            self.type_parameters,
+            self.type_args.len()
+        );
+        ensure!(
+            self.args.len() == self.parameters.len(),
+            "Script expects {} arguments, got {}",
+            self.parameters.len(),
+            self.args.len()
+        );
+
+        let args = self
+            .parameters
+            .iter()
+            .zip(self.args)
+            .enumerate()
+            .map(|(index, (param, arg))| {
+                script_arg(param, arg).ok_or_else(|| {
+                    anyhow!(
+                        "Argument {} does not match the parameter type {:?}",
+                        index,
+                        param
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ScriptTx::new(self.code, args, self.type_args, self.senders))
+    }
+}
+
+/// Converts `arg` into a `ScriptArg` if it has the type `param`.
+fn script_arg(param: &SignatureToken, arg: MoveValue) -> Option<ScriptArg> {
+    fn vector<T>(values: Vec<MoveValue>, f: impl Fn(MoveValue) -> Option<T>) -> Option<Vec<T>> {
+        values.into_iter().map(f).collect()
+    }
+
+    Some(match (param, arg) {
+        (SignatureToken::U8, MoveValue::U8(val)) => ScriptArg::U8(val),
+        (SignatureToken::U64, MoveValue::U64(val)) => ScriptArg::U64(val),
+        (SignatureToken::U128, MoveValue::U128(val)) => ScriptArg::U128(val),
+        (SignatureToken::Bool, MoveValue::Bool(val)) => ScriptArg::Bool(val),
+        (SignatureToken::Address, MoveValue::Address(val)) => ScriptArg::Address(val),
+        (SignatureToken::Vector(inner), MoveValue::Vector(values)) => match **inner {
+            SignatureToken::U8 => ScriptArg::VectorU8(vector(values, |val| match val {
+                MoveValue::U8(val) => Some(val),
+                _ => None,
+            })?),
+            SignatureToken::U64 => ScriptArg::VectorU64(vector(values, |val| match val {
+                MoveValue::U64(val) => Some(val),
+                _ => None,
+            })?),
+            SignatureToken::U128 => ScriptArg::VectorU128(vector(values, |val| match val {
+                MoveValue::U128(val) => Some(val),
+                _ => None,
+            })?),
+            SignatureToken::Bool => ScriptArg::VectorBool(vector(values, |val| match val {
+                MoveValue::Bool(val) => Some(val),
+                _ => None,
+            })?),
+            SignatureToken::Address => ScriptArg::VectorAddress(vector(values, |val| match val {
+                MoveValue::Address(val) => Some(val),
+                _ => None,
+            })?),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// Entry function of a published module + passed arguments and type parameters.
+pub struct FunctionTx {
+    module: ModuleId,
+    function: Identifier,
+    args: Vec<Value>,
+    type_args: Vec<TypeTag>,
+    senders: Vec<AccountAddress>,
+}
+
+/// Function transaction.
+impl FunctionTx {
+    /// Constructor.
+    ///
+    /// Senders are passed to the function as leading `&signer` arguments.
+    pub fn new(
+        module: ModuleId,
+        function: Identifier,
+        args: Vec<ScriptArg>,
+        type_args: Vec<TypeTag>,
+        senders: Vec<AccountAddress>,
+    ) -> Self {
+        FunctionTx {
+            module,
+            function,
+            args: args.into_iter().map(ScriptArg::into).collect(),
+            type_args,
+            senders,
+        }
+    }
+
+    /// Module of the function.
+    pub fn module(&self) -> &ModuleId {
+        &self.module
+    }
+
+    /// Function name.
+    pub fn function(&self) -> &Identifier {
+        &self.function
+    }
+
+    /// Parameters passed to the function.
+    pub fn args(&self) -> &[Value] {
+        &self.args
+    }
+
+    /// Type parameters passed to the function.
+    pub fn type_parameters(&self) -> &[TypeTag] {
+        &self.type_args
+    }
+
+    /// Convert into internal data.
+    #[allow(clippy::type_complexity)]
+    pub fn into_inner(
+        self,
+    ) -> (
+        ModuleId,
+        Identifier,
+        Vec<Value>,
+        Vec<TypeTag>,
+        Vec<AccountAddress>,
+    ) {
+        (
+            self.module,
+            self.function,
+            self.args,
+            self.type_args,
+            self.senders,
+        )
+    }
+}
+
+impl fmt::Debug for FunctionTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("module", &self.module)
+            .field("function", &self.function)
+            .field("args", &self.args)
+            .field("type_args", &self.type_args)
+            .field("senders", &self.senders)
+            .finish()
+    }
+}
+
+/// Gas used by a transaction split by phase.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(Serialize, Deserialize))]
+pub struct GasBreakdown {
+    /// Gas charged for the transaction size.
+    pub intrinsic: u64,
+    /// Gas charged for module publishing or script execution.
+    pub execution: u64,
+    /// Gas charged for writes to the global storage.
+    pub storage: u64,
+    /// Gas charged for the size of emitted events.
+    pub events: u64,
+}
+
+/// Emitted event: sender address, event type, serialized message and emitting module.
+pub type Event = (AccountAddress, TypeTag, Vec<u8>, Option<ModuleId>);
+
+/// Details of a failed transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-types", derive(Serialize, Deserialize))]
+pub struct VmError {
+    /// Error status code.
+    pub major_status: StatusCode,
+    /// Error sub status code, e.g. the abort code.
+    pub sub_status: Option<u64>,
+    /// Script or module where the error happened.
+    pub location: Location,
+    /// Functions and code offsets where the error happened.
+    pub offsets: Vec<(FunctionDefinitionIndex, CodeOffset)>,
+    /// Error message.
+    pub message: Option<String>,
+    /// Module which could not be found when linking, for `LINKER_ERROR`.
+    pub missing_module: Option<ModuleId>,
+}
+
+impl From<&VMError> for VmError {
+    fn from(err: &VMError) -> Self {
+        VmError {
+            major_status: err.major_status(),
+            sub_status: err.sub_status(),
+            location: err.location().clone(),
+            offsets: err.offsets().clone(),
+            message: err.message().cloned(),
+            missing_module: match err.location() {
+                Location::Module(id) if err.major_status() == StatusCode::LINKER_ERROR => {
+                    Some(id.clone())
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
 /// Move VM result.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde-types", derive(Serialize, Deserialize))]
 pub struct VmResult {
     /// Execution status code.
     pub status_code: StatusCode,
@@ -149,6 +571,17 @@ pub struct VmResult {
     pub sub_status: Option<u64>,
     /// Gas used.
     pub gas_used: u64,
+    /// Gas remaining when execution finished, before storage and event charges.
+    /// Equals the final remaining gas for transactions without such charges.
+    pub remaining_gas_after_execution: u64,
+    /// Events emitted by the transaction in emission order.
+    pub events: Vec<Event>,
+    /// Gas used split by phase.
+    pub gas_breakdown: GasBreakdown,
+    /// Coins to return to the sender for unused gas: `(max_gas_amount - gas_used) * gas_unit_price`.
+    pub refund: u128,
+    /// Error details if the transaction failed.
+    pub error: Option<VmError>,
 }
 
 impl VmResult {
@@ -158,8 +591,134 @@ impl VmResult {
             status_code,
             sub_status,
             gas_used,
+            remaining_gas_after_execution: 0,
+            events: Vec::new(),
+            gas_breakdown: GasBreakdown::default(),
+            refund: 0,
+            error: None,
         }
     }
+
+    /// Creates Vm result of the failed transaction.
+    pub(crate) fn from_error(err: &VMError, gas_used: u64) -> VmResult {
+        let mut result = VmResult::new(err.major_status(), err.sub_status(), gas_used);
+        result.error = Some(VmError::from(err));
+        result
+    }
+
+    /// Sets the refund for unused gas.
+    pub(crate) fn with_refund(mut self, gas: &Gas) -> VmResult {
+        self.refund = (gas.max_gas_amount - self.gas_used) as u128 * gas.gas_unit_price as u128;
+        self
+    }
+
+    /// Sets gas used split by phase.
+    pub(crate) fn with_gas_breakdown(mut self, gas_breakdown: GasBreakdown) -> VmResult {
+        self.gas_breakdown = gas_breakdown;
+        self
+    }
+
+    /// Sets events emitted by the transaction.
+    pub(crate) fn with_events(mut self, events: Vec<Event>) -> VmResult {
+        self.events = events;
+        self
+    }
+
+    /// Sets gas remaining when execution finished.
+    pub(crate) fn with_remaining_gas_after_execution(mut self, remaining_gas: u64) -> VmResult {
+        self.remaining_gas_after_execution = remaining_gas;
+        self
+    }
+}
+
+/// Defines how to handle publishing of a module which already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepublishPolicy {
+    /// Republishing is an error.
+    Reject,
+    /// Module can be replaced with a backward compatible one.
+    AllowCompatible,
+    /// Module can be replaced with any other one.
+    AllowAny,
+}
+
+impl Default for RepublishPolicy {
+    fn default() -> Self {
+        RepublishPolicy::AllowCompatible
+    }
+}
+
+/// Instructions which published modules and executed scripts must not contain.
+#[derive(Debug, Clone, Default)]
+pub struct OpcodePolicy {
+    forbidden: Vec<Opcodes>,
+}
+
+impl OpcodePolicy {
+    /// Policy which allows all instructions.
+    pub fn allow_all() -> OpcodePolicy {
+        OpcodePolicy::default()
+    }
+
+    /// Forbids the given instruction.
+    pub fn forbid(mut self, opcode: Opcodes) -> OpcodePolicy {
+        self.forbidden.push(opcode);
+        self
+    }
+
+    /// Returns `true` if no instruction is forbidden.
+    pub fn is_allow_all(&self) -> bool {
+        self.forbidden.is_empty()
+    }
+
+    /// Returns the first instruction of `code` forbidden by the policy.
+    pub fn find_forbidden<'a>(&self, code: &'a [Bytecode]) -> Option<&'a Bytecode> {
+        code.iter().find(|instruction| {
+            let key = instruction_key(instruction);
+            self.forbidden.iter().any(|opcode| *opcode as u8 == key)
+        })
+    }
+}
+
+/// Categories of transaction effects to be applied to the local state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EffectToggles {
+    /// Store resource changes.
+    pub resources: bool,
+    /// Store published modules.
+    pub modules: bool,
+    /// Pass emitted events to the event handler.
+    pub events: bool,
+    /// Apply native balance operations.
+    pub balances: bool,
+}
+
+impl EffectToggles {
+    /// Applies all effect categories.
+    pub fn all() -> EffectToggles {
+        EffectToggles {
+            resources: true,
+            modules: true,
+            events: true,
+            balances: true,
+        }
+    }
+
+    /// Applies only resource changes.
+    pub fn resources_only() -> EffectToggles {
+        EffectToggles {
+            resources: true,
+            modules: false,
+            events: false,
+            balances: false,
+        }
+    }
+}
+
+impl Default for EffectToggles {
+    fn default() -> Self {
+        EffectToggles::all()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
@@ -340,6 +899,26 @@ pub struct PublishPackageTx {
     address: AccountAddress,
 }
 
+impl TryFrom<Vec<ModuleTx>> for PublishPackageTx {
+    type Error = Error;
+
+    /// Creates a package from modules of the same sender.
+    fn try_from(modules: Vec<ModuleTx>) -> Result<Self, Self::Error> {
+        let address = match modules.first() {
+            Some(module) => module.sender,
+            None => bail!("Package must contain at least one module."),
+        };
+        ensure!(
+            modules.iter().all(|module| module.sender == address),
+            "All modules in the package must have the same sender."
+        );
+        Ok(PublishPackageTx {
+            modules: modules.into_iter().map(|module| module.code).collect(),
+            address,
+        })
+    }
+}
+
 impl PublishPackageTx {
     pub fn into_inner(self) -> (Vec<Vec<u8>>, AccountAddress) {
         (self.modules, self.address)
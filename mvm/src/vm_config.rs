@@ -1,18 +1,31 @@
 use crate::gas_schedule::cost_table;
 use move_core_types::gas_schedule::CostTable;
+use move_vm_types::loaded_data::runtime_types::TYPE_DEPTH_MAX;
 use parity_scale_codec::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
+/// Default limit on the size of a published module blob.
+pub const DEFAULT_MAX_MODULE_BYTES: u64 = 64 * 1024;
+
+/// Default limit on the nesting depth of transaction type arguments.
+pub const DEFAULT_MAX_TYPE_DEPTH: u64 = TYPE_DEPTH_MAX as u64;
+
 /// Defines all the on chain configuration data needed by VM.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize, Encode, Decode)]
 pub struct VmConfig {
     pub gas_schedule: CostTable,
+    /// Maximum size in bytes of a published module.
+    pub max_module_bytes: u64,
+    /// Maximum nesting depth of transaction type arguments.
+    pub max_type_depth: u64,
 }
 
 impl Default for VmConfig {
     fn default() -> Self {
         VmConfig {
             gas_schedule: cost_table(),
+            max_module_bytes: DEFAULT_MAX_MODULE_BYTES,
+            max_type_depth: DEFAULT_MAX_TYPE_DEPTH,
         }
     }
 }
@@ -24,52 +37,73 @@ pub mod loader {
     use alloc::vec::Vec;
     use anyhow::{Error, Result};
     use move_core_types::account_address::AccountAddress;
+    use move_core_types::gas_schedule::CostTable;
     use move_core_types::identifier::Identifier;
     use move_core_types::language_storage::StructTag;
     use parity_scale_codec::{Decode, Encode};
 
     const IDENTIFIER: &str = "MVMConfig";
+    /// Configs stored under this name contain only the gas schedule.
+    const LEGACY_NAME: &str = IDENTIFIER;
+    /// Configs stored under this name contain the whole `VmConfig`.
+    const NAME: &str = "MVMConfigV2";
     const CONFIG_ADDRESS_STR: &str = "0xA550C18";
 
     fn config_address() -> AccountAddress {
         AccountAddress::from_hex_literal(CONFIG_ADDRESS_STR).expect("failed to get address")
     }
 
-    fn make_access_path() -> AccessPath {
+    fn make_access_path(name: &str) -> AccessPath {
         let address = config_address();
-        let id = Identifier::new(IDENTIFIER).expect("failed to get Identifier");
+        let module = Identifier::new(IDENTIFIER).expect("failed to get Identifier");
+        let name = Identifier::new(name).expect("failed to get Identifier");
 
         AccessPath::new(
             address,
             AccessPath::resource_access_vec(&StructTag {
                 address,
-                module: id.clone(),
-                name: id,
+                module,
+                name,
                 type_params: vec![],
             }),
         )
     }
 
-    fn make_storage_key() -> Vec<u8> {
-        let path = make_access_path();
+    fn make_storage_key(name: &str) -> Vec<u8> {
+        let path = make_access_path(name);
         let mut key = Vec::with_capacity(AccountAddress::LENGTH + path.path.len());
         key.extend_from_slice(&path.address.to_u8());
         key.extend_from_slice(&path.path);
         key
     }
 
+    /// Returns the storage key of configs stored before the vm limits were introduced.
+    pub fn legacy_storage_key() -> Vec<u8> {
+        make_storage_key(LEGACY_NAME)
+    }
+
     /// Loads vm config from storage. Returns default configuration if the config does not exists in the storage.
+    ///
+    /// Legacy configs provide only the gas schedule, the limits are set to their defaults.
     pub fn load_vm_config<S: Storage>(storage: &S) -> Result<VmConfig, Error> {
-        if let Some(blob) = storage.get(&make_storage_key()) {
-            let mut input = blob.as_slice();
-            VmConfig::decode(&mut input).map_err(|_| Error::msg("failed to decode VMConfig."))
+        if let Some(blob) = storage.get(&make_storage_key(NAME)) {
+            VmConfig::decode(&mut blob.as_slice())
+                .map_err(|_| Error::msg("failed to decode VMConfig."))
+        } else if let Some(blob) = storage.get(&legacy_storage_key()) {
+            let gas_schedule = CostTable::decode(&mut blob.as_slice())
+                .map_err(|_| Error::msg("failed to decode legacy VMConfig."))?;
+            Ok(VmConfig {
+                gas_schedule,
+                ..VmConfig::default()
+            })
         } else {
             Ok(VmConfig::default())
         }
     }
 
-    /// Stores vm configuration to the storage.
+    /// Stores vm configuration to the storage, replacing the legacy config if any.
     pub fn store_vm_config<S: Storage>(storage: &S, config: &VmConfig) {
-        storage.insert(&make_storage_key(), &config.encode());
+        storage.insert(&make_storage_key(NAME), &config.encode());
+        storage.remove(&legacy_storage_key());
     }
 }
@@ -0,0 +1,129 @@
+use move_core_types::account_address::AccountAddress;
+use move_core_types::identifier::Identifier;
+use move_core_types::language_storage::ResourceKey;
+use move_core_types::language_storage::{ModuleId, StructTag, TypeTag, CORE_CODE_ADDRESS};
+use mvm::access_path::{AccessPath, AccessPathHasher, DefaultAccessPathHasher};
+use mvm::data::AccessKey;
+
+#[test]
+fn test_decode_resource_key() {
+    let addr = AccountAddress::random();
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Account").unwrap(),
+        name: Identifier::new("Balance").unwrap(),
+        type_params: vec![TypeTag::U64],
+    };
+
+    let path = AccessPath::decode_key(AccessKey::from((&addr, &tag)).as_ref()).unwrap();
+    assert_eq!(path.address, addr);
+    assert_eq!(path.resource_tag(), Some(tag));
+    assert_eq!(path.module_id(), None);
+}
+
+#[test]
+fn test_decode_module_key() {
+    let id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+
+    let path = AccessPath::decode_key(AccessKey::from(&id).as_ref()).unwrap();
+    assert_eq!(path, AccessPath::code_access_path(&id));
+    assert_eq!(path.module_id(), Some(id));
+    assert_eq!(path.resource_tag(), None);
+}
+
+#[test]
+fn test_decode_invalid_key() {
+    assert!(AccessPath::decode_key(&[]).is_err());
+    assert!(AccessPath::decode_key(&[0x1, 0x2, 0x3]).is_err());
+}
+
+fn store_u64() -> StructTag {
+    StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    }
+}
+
+fn core_address_bytes() -> Vec<u8> {
+    let mut bytes = vec![0; AccountAddress::LENGTH - 1];
+    bytes.push(1);
+    bytes
+}
+
+#[test]
+fn test_default_hasher_golden() {
+    let mut resource_path = vec![1];
+    resource_path.extend(core_address_bytes());
+    resource_path.extend(b"\x05Store\x03U64\x00");
+    assert_eq!(AccessPath::resource_access_vec(&store_u64()), resource_path);
+    assert_eq!(
+        DefaultAccessPathHasher.resource_path(&store_u64()),
+        resource_path
+    );
+
+    let id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let mut code_path = vec![0];
+    code_path.extend(core_address_bytes());
+    code_path.extend(b"\x05Store");
+    assert_eq!(AccessPath::code_access_path(&id).path, code_path);
+    assert_eq!(DefaultAccessPathHasher.code_path(&id), code_path);
+}
+
+struct PrefixedHasher;
+
+impl AccessPathHasher for PrefixedHasher {
+    fn resource_path(&self, tag: &StructTag) -> Vec<u8> {
+        let mut path = b"res".to_vec();
+        path.extend(tag.access_vector());
+        path
+    }
+
+    fn code_path(&self, id: &ModuleId) -> Vec<u8> {
+        let mut path = b"code".to_vec();
+        path.extend(id.access_vector());
+        path
+    }
+}
+
+#[test]
+fn test_custom_hasher() {
+    let addr = AccountAddress::random();
+    let key = ResourceKey::new(addr, store_u64());
+
+    let path = AccessPath::resource_access_path_with(&key, &PrefixedHasher);
+    assert_eq!(path.address, addr);
+    assert!(path.path.starts_with(b"res"));
+    assert_ne!(path, AccessPath::resource_access_path(&key));
+
+    let id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let path = AccessPath::code_access_path_with(&id, &PrefixedHasher);
+    assert_eq!(path.address, CORE_CODE_ADDRESS);
+    assert!(path.path.starts_with(b"code"));
+}
+
+#[test]
+fn test_full_width_address_key_golden() {
+    let addr = AccountAddress::new([0xAB; 32]);
+    assert_eq!(AccountAddress::LENGTH, 32);
+
+    let mut key = vec![0xAB; 32];
+    key.push(1);
+    key.extend(core_address_bytes());
+    key.extend(b"\x05Store\x03U64\x00");
+    let access_key = AccessKey::from((&addr, &store_u64()));
+    assert_eq!(access_key.as_ref(), key.as_slice());
+    assert!(access_key.as_ref().starts_with(addr.as_ref()));
+
+    let path = AccessPath::decode_key(&key).unwrap();
+    assert_eq!(path.address, addr);
+    assert_eq!(path.resource_tag(), Some(store_u64()));
+
+    let id = ModuleId::new(addr, Identifier::new("Store").unwrap());
+    let mut key = vec![0];
+    key.extend(vec![0xAB; 32]);
+    key.extend(b"\x05Store");
+    assert_eq!(AccessKey::from(&id).as_ref(), key.as_slice());
+    assert_eq!(AccessPath::decode_key(&key).unwrap().module_id(), Some(id));
+}
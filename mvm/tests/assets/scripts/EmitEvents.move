@@ -0,0 +1,10 @@
+script {
+    use 0x1::Event;
+    use 0x1::EventProxy;
+
+    fun emit_events(signer: &signer, val: u64) {
+        Event::emit(signer, EventProxy::create_val(val));
+        Event::emit(signer, EventProxy::create_val(val + 1));
+        Event::emit(signer, EventProxy::create_val(val + 2));
+    }
+}
@@ -0,0 +1,10 @@
+script {
+    use 0x1::Event;
+    use 0x1::EventProxy;
+    use 0x1::Store;
+
+    fun store_and_emit(signer: &signer, val: u64) {
+        Store::store_u64(signer, val);
+        Event::emit(signer, EventProxy::create_val(val));
+    }
+}
@@ -0,0 +1,14 @@
+script {
+    use 0x1::Account;
+    use 0x1::Coins;
+    use 0x1::Event;
+    use 0x1::EventProxy;
+    use 0x1::Store;
+
+    fun store_emit_transfer(alice: &signer, bob: &signer, amount: u128) {
+        Store::store_u64(alice, 13);
+        Event::emit(alice, EventProxy::create_val(13));
+        let usdt = Account::deposit_native<Coins::USDT>(alice, amount);
+        Account::withdraw_native<Coins::USDT>(bob, usdt);
+    }
+}
@@ -129,6 +129,24 @@ pub fn store_u64_script(addr: AccountAddress, args: u64) -> ScriptTx {
     )
 }
 
+pub fn store_and_emit_script(addr: AccountAddress, args: u64) -> ScriptTx {
+    ScriptTx::new(
+        include_bytes!("../assets/target/scripts/store_and_emit.mv").to_vec(),
+        vec![ScriptArg::U64(args)],
+        vec![],
+        vec![addr],
+    )
+}
+
+pub fn emit_events_script(addr: AccountAddress, args: u64) -> ScriptTx {
+    ScriptTx::new(
+        include_bytes!("../assets/target/scripts/emit_events.mv").to_vec(),
+        vec![ScriptArg::U64(args)],
+        vec![],
+        vec![addr],
+    )
+}
+
 pub fn test_transfer_script(alice: AccountAddress, bob: AccountAddress, amount: u128) -> ScriptTx {
     ScriptTx::new(
         include_bytes!("../assets/target/scripts/test_balance_transfer.mv").to_vec(),
@@ -139,14 +157,13 @@ pub fn test_transfer_script(alice: AccountAddress, bob: AccountAddress, amount:
 }
 
 pub fn reg_coin_script(ty: TypeTag, denom: &str, decimals: u8) -> ScriptTx {
-    ScriptTx::new(
+    ScriptTx::system(
         include_bytes!("../assets/target/scripts/register_coin.mv").to_vec(),
         vec![
             ScriptArg::VectorU8(denom.as_bytes().to_vec()),
             ScriptArg::U8(decimals),
         ],
         vec![ty],
-        vec![CORE_CODE_ADDRESS],
     )
 }
 
@@ -178,6 +195,19 @@ pub fn test_balance_script(
     )
 }
 
+pub fn store_emit_transfer_script(
+    alice: AccountAddress,
+    bob: AccountAddress,
+    amount: u128,
+) -> ScriptTx {
+    ScriptTx::new(
+        include_bytes!("../assets/target/scripts/store_emit_transfer.mv").to_vec(),
+        vec![ScriptArg::U128(amount)],
+        vec![],
+        vec![alice, bob],
+    )
+}
+
 pub fn stdlib_package() -> ModulePackage {
     ModulePackage::try_from(&include_bytes!("../assets/target/packages/stdlib.pac")[..]).unwrap()
 }
@@ -6,7 +6,9 @@ use move_core_types::account_address::AccountAddress;
 use move_core_types::language_storage::{ModuleId, TypeTag};
 use move_core_types::vm_status::StatusCode;
 use move_vm_types::natives::balance::Balance;
-use mvm::data::{BalanceAccess, EventHandler, ExecutionContext, Oracle, Storage};
+use mvm::data::{
+    BalanceAccess, BalanceError, EventHandler, ExecutionContext, Oracle, Storage, StorageIter,
+};
 use mvm::mvm::Mvm;
 use mvm::types::{ModuleTx, ScriptTx};
 use mvm::Vm;
@@ -47,6 +49,23 @@ impl Storage for StorageMock {
         let mut data = self.data.borrow_mut();
         data.remove(key);
     }
+
+    fn multi_get(&self, keys: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
+        let data = self.data.borrow();
+        keys.iter().map(|key| data.get(key).cloned()).collect()
+    }
+}
+
+impl StorageIter for StorageMock {
+    fn scan_prefix(&self, prefix: &[u8]) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + '_> {
+        let data = self.data.borrow();
+        let entries = data
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect::<Vec<_>>();
+        Box::new(entries.into_iter())
+    }
 }
 
 #[derive(Clone, Default)]
@@ -115,24 +134,34 @@ impl BalanceAccess for BankMock {
             .and_then(|acc| acc.get(ticker).cloned())
     }
 
-    fn deposit(&self, address: &AccountAddress, ticker: &str, amount: Balance) {
+    fn deposit(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError> {
         let mut acc_map = self.balances.borrow_mut();
-        let acc = acc_map.entry(*address).or_insert_with(HashMap::new);
-        let val = acc.entry(ticker.to_owned()).or_insert(0);
-        if *val < amount {
-            panic!(
-                "Not enough currency in the account [{}::{}] You need {} units in stock {}",
-                address, ticker, amount, val
-            );
-        }
-        *val -= amount;
+        let val = acc_map
+            .get_mut(address)
+            .and_then(|acc| acc.get_mut(ticker))
+            .ok_or(BalanceError::Unavailable)?;
+        *val = val
+            .checked_sub(amount)
+            .ok_or(BalanceError::InsufficientFunds)?;
+        Ok(())
     }
 
-    fn withdraw(&self, address: &AccountAddress, ticker: &str, amount: Balance) {
+    fn withdraw(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError> {
         let mut acc_map = self.balances.borrow_mut();
         let acc = acc_map.entry(*address).or_insert_with(HashMap::new);
         let val = acc.entry(ticker.to_owned()).or_insert(0);
-        *val += amount;
+        *val = val.checked_add(amount).ok_or(BalanceError::Overflow)?;
+        Ok(())
     }
 }
 
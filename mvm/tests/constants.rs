@@ -0,0 +1,27 @@
+mod common;
+
+use common::assets::*;
+use move_core_types::value::{MoveTypeLayout, MoveValue};
+use mvm::constants::module_constants;
+use vm::file_format::Constant;
+use vm::CompiledModule;
+
+#[test]
+fn test_module_constants() {
+    let module = CompiledModule::deserialize(store_module().code()).unwrap();
+    let mut module = module.into_inner();
+    module
+        .constant_pool
+        .push(Constant::serialize_constant(&MoveTypeLayout::U64, &MoveValue::U64(42)).unwrap());
+    let module = module.freeze().unwrap();
+    let mut blob = Vec::new();
+    module.serialize(&mut blob).unwrap();
+
+    let constants = module_constants(&blob).unwrap();
+    let (layout, data) = constants.last().unwrap();
+    assert!(matches!(layout, MoveTypeLayout::U64));
+    assert_eq!(
+        MoveValue::simple_deserialize(data, layout).unwrap(),
+        MoveValue::U64(42)
+    );
+}
@@ -0,0 +1,740 @@
+mod common;
+
+use common::assets::*;
+use common::mock::{OracleMock, StorageMock};
+use move_core_types::account_address::AccountAddress;
+use move_core_types::identifier::Identifier;
+use move_core_types::language_storage::{ModuleId, StructTag, TypeTag, CORE_CODE_ADDRESS};
+use move_core_types::value::{MoveStruct, MoveStructLayout, MoveTypeLayout, MoveValue};
+use move_core_types::vm_status::StatusCode;
+use move_vm_runtime::data_cache::RemoteCache;
+use move_vm_types::natives::balance::{
+    Balance, BalanceOperation, MasterOfCoin, NativeBalance, WalletId,
+};
+use mvm::data::{
+    module_key, resource_key, AccessKey, BalanceAccess, BalanceError, Bank, BankConfig,
+    ExecutionContext, Resolve, State, StateBuilder, StateSession, Storage, TimeUnit, WriteBatch,
+    WriteEffects,
+};
+use mvm::memory::{InMemoryBank, InMemoryOracle, InMemoryStorage, OverlayStore};
+use std::cell::Cell;
+use vm::errors::PartialVMResult;
+
+#[test]
+fn test_state_builder() {
+    let module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let resource = bcs::to_bytes(&13u64).unwrap();
+
+    let state = StateBuilder::new()
+        .module(&module_id, store_module().code().to_vec())
+        .resource(&CORE_CODE_ADDRESS, &tag, resource.clone())
+        .build();
+
+    assert_eq!(
+        state.get_module(&module_id).unwrap().unwrap(),
+        store_module().code()
+    );
+    assert_eq!(
+        state
+            .get_resource(&CORE_CODE_ADDRESS, &tag)
+            .unwrap()
+            .unwrap(),
+        resource
+    );
+}
+
+#[test]
+fn test_get_resource_decoded() {
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let state = StateBuilder::new()
+        .resource(&CORE_CODE_ADDRESS, &tag, bcs::to_bytes(&13u64).unwrap())
+        .build();
+    let layout = MoveTypeLayout::Struct(MoveStructLayout::new(vec![MoveTypeLayout::U64]));
+
+    assert_eq!(
+        state
+            .get_resource_decoded(&CORE_CODE_ADDRESS, &tag, &layout)
+            .unwrap(),
+        Some(MoveValue::Struct(MoveStruct::new(vec![MoveValue::U64(13)])))
+    );
+    assert_eq!(
+        state
+            .get_resource_decoded(&AccountAddress::random(), &tag, &layout)
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_import_state() {
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let (addr, other) = (AccountAddress::random(), AccountAddress::random());
+    let state = State::new(InMemoryStorage::default(), InMemoryOracle::default());
+    state.import_state(vec![
+        (addr, tag.clone(), bcs::to_bytes(&13u64).unwrap()),
+        (other, tag.clone(), bcs::to_bytes(&42u64).unwrap()),
+    ]);
+
+    let layout = MoveTypeLayout::Struct(MoveStructLayout::new(vec![MoveTypeLayout::U64]));
+    assert_eq!(
+        state.get_resource_decoded(&addr, &tag, &layout).unwrap(),
+        Some(MoveValue::Struct(MoveStruct::new(vec![MoveValue::U64(13)])))
+    );
+    assert_eq!(
+        state.get_resource(&other, &tag).unwrap(),
+        Some(bcs::to_bytes(&42u64).unwrap())
+    );
+
+    let module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let code = store_module().code().to_vec();
+    state.import_modules(vec![(module_id.clone(), code.clone())]);
+    assert_eq!(state.get_module(&module_id).unwrap(), Some(code));
+}
+
+#[test]
+fn test_get_resources() {
+    let tag = |name: &str| StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new(name).unwrap(),
+        type_params: vec![],
+    };
+    let addr = AccountAddress::random();
+    let state = State::new(StorageMock::new(), OracleMock::default());
+    state.insert(
+        AccessKey::from((&addr, &tag("U64"))),
+        bcs::to_bytes(&13u64).unwrap(),
+    );
+    state.insert(
+        AccessKey::from((&addr, &tag("U128"))),
+        bcs::to_bytes(&42u128).unwrap(),
+    );
+
+    let blobs = state
+        .get_resources(&addr, &[tag("U128"), tag("Address"), tag("U64")])
+        .unwrap();
+    assert_eq!(
+        blobs,
+        vec![
+            Some(bcs::to_bytes(&42u128).unwrap()),
+            None,
+            Some(bcs::to_bytes(&13u64).unwrap()),
+        ]
+    );
+}
+
+#[test]
+fn test_write_batch() {
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let resource = bcs::to_bytes(&13u64).unwrap();
+    let state = StateBuilder::new().build();
+
+    let mut batch = WriteBatch::new();
+    batch.insert(
+        AccessKey::from((&CORE_CODE_ADDRESS, &tag)),
+        resource.clone(),
+    );
+    assert_eq!(batch.len(), 1);
+    batch.rollback();
+    assert!(state
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .is_none());
+
+    let mut batch = WriteBatch::new();
+    batch.insert(
+        AccessKey::from((&CORE_CODE_ADDRESS, &tag)),
+        resource.clone(),
+    );
+    batch.commit(&state);
+    assert_eq!(
+        state
+            .get_resource(&CORE_CODE_ADDRESS, &tag)
+            .unwrap()
+            .unwrap(),
+        resource
+    );
+
+    let mut batch = WriteBatch::new();
+    batch.delete(AccessKey::from((&CORE_CODE_ADDRESS, &tag)));
+    batch.commit(&state);
+    assert!(state
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_execution_context_accessors() {
+    let context = ExecutionContext::new(1_600_000_000, 42);
+    assert_eq!(context.current_time(), 1_600_000_000);
+    assert_eq!(context.current_block(), 42);
+}
+
+#[test]
+fn test_time_unit() {
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Time").unwrap(),
+        name: Identifier::new("CurrentTimestamp").unwrap(),
+        type_params: vec![],
+    };
+    let state = StateBuilder::new().build();
+    let micros = 1_600_000_123_456_789;
+    let timestamp = |time_unit| {
+        let context = ExecutionContext::new(micros, 1).with_time_unit(time_unit);
+        let blob = StateSession::new(&state, context)
+            .get_resource(&CORE_CODE_ADDRESS, &tag)
+            .unwrap()
+            .unwrap();
+        bcs::from_bytes::<u64>(&blob).unwrap()
+    };
+
+    assert_eq!(timestamp(TimeUnit::default()), micros);
+    assert_eq!(timestamp(TimeUnit::Micros), micros);
+    assert_eq!(timestamp(TimeUnit::Millis), 1_600_000_123_456);
+    assert_eq!(timestamp(TimeUnit::Seconds), 1_600_000_123);
+}
+
+#[test]
+fn test_bank_config() {
+    let std_address = AccountAddress::from_hex_literal("0x2").unwrap();
+    let account = AccountAddress::random();
+    let access = InMemoryBank::default();
+    access.set_balance(&account, "PONT", 10);
+    access.set_balance(&account, "BTC", 3);
+
+    let bank = Bank::new(
+        access,
+        BankConfig {
+            std_address,
+            ..BankConfig::pontem()
+        },
+    );
+    let wallet = |address, module: &str, name: &str| WalletId {
+        address: account,
+        tag: StructTag {
+            address,
+            module: Identifier::new(module).unwrap(),
+            name: Identifier::new(name).unwrap(),
+            type_params: vec![],
+        },
+    };
+
+    assert_eq!(
+        (&bank).get_balance(&wallet(std_address, "PONT", "T")),
+        Some(10)
+    );
+    assert_eq!(
+        (&bank).get_balance(&wallet(std_address, "Coins", "BTC")),
+        Some(3)
+    );
+    assert_eq!(
+        (&bank).get_balance(&wallet(CORE_CODE_ADDRESS, "PONT", "T")),
+        None
+    );
+    assert_eq!(bank.access().get_balance(&account, "PONT"), Some(10));
+}
+
+#[test]
+fn test_bank_coin_standards() {
+    let account = AccountAddress::random();
+    let access = InMemoryBank::default();
+    access.set_balance(&account, "BTC", 3);
+    access.set_balance(&account, "ETH", 7);
+
+    let ident = |name: &str| Identifier::new(name).unwrap();
+    let tag = |address, module: &str, name: &str, type_params| StructTag {
+        address,
+        module: ident(module),
+        name: ident(name),
+        type_params,
+    };
+    let bank = Bank::new(
+        access,
+        BankConfig {
+            coin_standards: vec![
+                (ident("Dfinance"), ident("T")),
+                (ident("Token"), ident("Coin")),
+            ],
+            ..BankConfig::pontem()
+        },
+    );
+    let balance = |tag| {
+        (&bank).get_balance(&WalletId {
+            address: account,
+            tag,
+        })
+    };
+
+    let btc = TypeTag::Struct(tag(CORE_CODE_ADDRESS, "Coins", "BTC", vec![]));
+    let eth = TypeTag::Struct(tag(CORE_CODE_ADDRESS, "Coins", "ETH", vec![]));
+    let old = tag(CORE_CODE_ADDRESS, "Dfinance", "T", vec![btc.clone()]);
+    let new = tag(CORE_CODE_ADDRESS, "Token", "Coin", vec![eth.clone()]);
+    assert_eq!(balance(old), Some(3));
+    assert_eq!(balance(new.clone()), Some(7));
+
+    // A lock defined outside the standard library wrapping the new standard's coin.
+    let locked = tag(
+        AccountAddress::random(),
+        "Vault",
+        "Locked",
+        vec![TypeTag::Struct(new)],
+    );
+    assert_eq!(balance(locked), Some(7));
+
+    // Coins are only found through a coin standard.
+    let locked = tag(AccountAddress::random(), "Vault", "Locked", vec![eth]);
+    assert_eq!(balance(locked), None);
+    let unknown = tag(CORE_CODE_ADDRESS, "Other", "Coin", vec![btc]);
+    assert_eq!(balance(unknown), None);
+}
+
+#[derive(Default)]
+struct CountingStorage {
+    inner: InMemoryStorage,
+    reads: Cell<usize>,
+}
+
+impl Storage for CountingStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.reads.set(self.reads.get() + 1);
+        self.inner.get(key)
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) {
+        self.inner.insert(key, value);
+    }
+
+    fn remove(&self, key: &[u8]) {
+        self.inner.remove(key);
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        self.inner.contains(key)
+    }
+}
+
+#[test]
+fn test_state_view() {
+    let store = StorageMock::new();
+    let oracle = OracleMock::default();
+    let addr = AccountAddress::random();
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    store.insert(&resource_key(&addr, &tag), &[0x1]);
+
+    let view = State::view(&store, &oracle);
+    for _ in 0..3 {
+        assert_eq!(view.get_resource(&addr, &tag).unwrap(), Some(vec![0x1]));
+    }
+    let other = AccountAddress::random();
+    assert_eq!(view.get_resource(&other, &tag).unwrap(), None);
+
+    // The view reads through to the borrowed storage.
+    store.remove(&resource_key(&addr, &tag));
+    assert_eq!(view.get_resource(&addr, &tag).unwrap(), None);
+}
+
+#[test]
+fn test_resource_exists() {
+    let addr = AccountAddress::random();
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let state = State::new(CountingStorage::default(), InMemoryOracle::default());
+    assert!(!state.resource_exists(&addr, &tag));
+
+    // The blob is not a valid `U64`, existence does not depend on its content.
+    state.insert(AccessKey::from((&addr, &tag)), vec![0x1]);
+    assert!(state.resource_exists(&addr, &tag));
+    assert!(!state.resource_exists(&AccountAddress::random(), &tag));
+    assert_eq!(state.store().reads.get(), 0);
+
+    state.delete(AccessKey::from((&addr, &tag)));
+    assert!(!state.resource_exists(&addr, &tag));
+
+    let key = resource_key(&addr, &tag);
+    let overlay = OverlayStore::new(InMemoryStorage::default());
+    overlay.base().insert(&key, &[0x1]);
+    assert!(overlay.contains(&key));
+    overlay.remove(&key);
+    assert!(!overlay.contains(&key));
+}
+
+#[test]
+fn test_module_cache() {
+    let module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let code = store_module().code().to_vec();
+    let state =
+        State::with_cache_capacity(CountingStorage::default(), InMemoryOracle::default(), 1);
+    state.insert(AccessKey::from(&module_id), code.clone());
+
+    for _ in 0..10 {
+        assert_eq!(state.get_module(&module_id).unwrap(), Some(code.clone()));
+    }
+    assert_eq!(state.store().reads.get(), 1);
+
+    state.insert(AccessKey::from(&module_id), vec![0x1]);
+    assert_eq!(state.get_module(&module_id).unwrap(), Some(vec![0x1]));
+    assert_eq!(state.store().reads.get(), 2);
+
+    let uncached = State::new(CountingStorage::default(), InMemoryOracle::default());
+    uncached.insert(AccessKey::from(&module_id), code);
+    for _ in 0..10 {
+        uncached.get_module(&module_id).unwrap();
+    }
+    assert_eq!(uncached.store().reads.get(), 10);
+}
+
+#[test]
+fn test_transaction_origin() {
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Transaction").unwrap(),
+        name: Identifier::new("Sender").unwrap(),
+        type_params: vec![],
+    };
+    let origin = AccountAddress::random();
+    let state = StateBuilder::new().build();
+
+    let session = StateSession::new(&state, ExecutionContext::new(10, 1000).with_origin(origin));
+    let blob = session
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .unwrap();
+    assert_eq!(bcs::from_bytes::<AccountAddress>(&blob).unwrap(), origin);
+
+    let session = StateSession::new(&state, ExecutionContext::new(10, 1000));
+    assert!(session
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_block_hash_and_chain_id() {
+    let tag = |module: &str, name: &str| StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new(module).unwrap(),
+        name: Identifier::new(name).unwrap(),
+        type_params: vec![],
+    };
+    let state = StateBuilder::new().build();
+
+    let context = ExecutionContext::new(10, 1000)
+        .with_block_hash([7; 32])
+        .with_chain_id(42);
+    let session = StateSession::new(&state, context);
+    let blob = session
+        .get_resource(&CORE_CODE_ADDRESS, &tag("Block", "BlockHash"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(bcs::from_bytes::<Vec<u8>>(&blob).unwrap(), vec![7; 32]);
+    let blob = session
+        .get_resource(&CORE_CODE_ADDRESS, &tag("Chain", "ChainId"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(bcs::from_bytes::<u64>(&blob).unwrap(), 42);
+
+    let session = StateSession::new(&state, ExecutionContext::new(10, 1000));
+    assert!(session
+        .get_resource(&CORE_CODE_ADDRESS, &tag("Block", "BlockHash"))
+        .unwrap()
+        .is_none());
+    assert!(session
+        .get_resource(&CORE_CODE_ADDRESS, &tag("Chain", "ChainId"))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_gas_unit_price() {
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Transaction").unwrap(),
+        name: Identifier::new("GasUnitPrice").unwrap(),
+        type_params: vec![],
+    };
+    let state = StateBuilder::new().build();
+
+    let context = ExecutionContext::new(10, 1000).with_gas_unit_price(3);
+    let session = StateSession::new(&state, context);
+    let blob = session
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .unwrap();
+    assert_eq!(bcs::from_bytes::<u64>(&blob).unwrap(), 3);
+
+    let session = StateSession::new(&state, ExecutionContext::new(10, 1000));
+    assert!(session
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_overlay_store() {
+    let base = InMemoryStorage::default();
+    base.insert(b"a", b"1");
+    base.insert(b"b", b"2");
+
+    let overlay = OverlayStore::new(base);
+    // Read-through.
+    assert_eq!(overlay.get(b"a"), Some(b"1".to_vec()));
+
+    // Shadowing.
+    overlay.insert(b"a", b"10");
+    overlay.insert(b"c", b"3");
+    assert_eq!(overlay.get(b"a"), Some(b"10".to_vec()));
+    assert_eq!(overlay.get(b"c"), Some(b"3".to_vec()));
+
+    // Tombstones.
+    overlay.remove(b"b");
+    assert_eq!(overlay.get(b"b"), None);
+
+    assert_eq!(overlay.base().get(b"a"), Some(b"1".to_vec()));
+    assert_eq!(overlay.base().get(b"b"), Some(b"2".to_vec()));
+    assert_eq!(overlay.base().get(b"c"), None);
+
+    let changes = overlay.into_changes();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(changes.get(&b"a"[..]), Some(&Some(b"10".to_vec())));
+    assert_eq!(changes.get(&b"b"[..]), Some(&None));
+
+    let overlay = OverlayStore::new(InMemoryStorage::default());
+    overlay.insert(b"a", b"1");
+    overlay.discard();
+    assert_eq!(overlay.get(b"a"), None);
+    assert!(overlay.into_changes().is_empty());
+}
+
+struct Beacon;
+
+impl Resolve for Beacon {
+    fn resolve(
+        &self,
+        address: &AccountAddress,
+        tag: &StructTag,
+    ) -> PartialVMResult<Option<Vec<u8>>> {
+        if address == &CORE_CODE_ADDRESS && tag.module.as_str() == "Random" {
+            Ok(Some(vec![4]))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[test]
+fn test_custom_resolver() {
+    let tag = |module: &str, name: &str| StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new(module).unwrap(),
+        name: Identifier::new(name).unwrap(),
+        type_params: vec![],
+    };
+    let state = StateBuilder::new()
+        .resource(&CORE_CODE_ADDRESS, &tag("Store", "U64"), vec![13])
+        .resource(&CORE_CODE_ADDRESS, &tag("Random", "Beacon"), vec![1])
+        .build();
+
+    let mut session = StateSession::new(&state, ExecutionContext::new(10, 1000));
+    assert_eq!(
+        session
+            .get_resource(&CORE_CODE_ADDRESS, &tag("Random", "Beacon"))
+            .unwrap(),
+        Some(vec![1])
+    );
+
+    session.push_resolver(Box::new(Beacon));
+    assert_eq!(
+        session
+            .get_resource(&CORE_CODE_ADDRESS, &tag("Random", "Beacon"))
+            .unwrap(),
+        Some(vec![4])
+    );
+    assert_eq!(
+        session
+            .get_resource(&CORE_CODE_ADDRESS, &tag("Store", "U64"))
+            .unwrap(),
+        Some(vec![13])
+    );
+    assert_eq!(
+        session
+            .get_resource(&CORE_CODE_ADDRESS, &tag("Block", "BlockMetadata"))
+            .unwrap(),
+        Some(1000u64.to_le_bytes().to_vec())
+    );
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let addr = AccountAddress::random();
+    let state = State::with_cache_capacity(StorageMock::new(), OracleMock::default(), 1);
+    state.insert(AccessKey::from(&module_id), store_module().code().to_vec());
+    state.insert(
+        AccessKey::from((&addr, &tag)),
+        bcs::to_bytes(&13u64).unwrap(),
+    );
+
+    let snapshot = state.snapshot();
+    assert_eq!(snapshot.len(), 2);
+
+    state.insert(
+        AccessKey::from((&addr, &tag)),
+        bcs::to_bytes(&42u64).unwrap(),
+    );
+    state.delete(AccessKey::from(&module_id));
+    state.insert(
+        AccessKey::from((&AccountAddress::random(), &tag)),
+        bcs::to_bytes(&1u64).unwrap(),
+    );
+
+    state.restore(snapshot.clone());
+    assert_eq!(state.snapshot(), snapshot);
+    assert_eq!(
+        state.get_resource(&addr, &tag).unwrap(),
+        Some(bcs::to_bytes(&13u64).unwrap())
+    );
+    assert_eq!(
+        state.get_module(&module_id).unwrap(),
+        Some(store_module().code().to_vec())
+    );
+}
+
+#[test]
+fn test_storage_keys() {
+    let store = StorageMock::new();
+    let state = State::new(store.clone(), OracleMock::default());
+    let module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let address = AccountAddress::random();
+    let resource = bcs::to_bytes(&13u64).unwrap();
+
+    state.insert(AccessKey::from((&address, &tag)), resource.clone());
+    state.insert(AccessKey::from(&module_id), store_module().code().to_vec());
+
+    assert_eq!(store.get(&resource_key(&address, &tag)), Some(resource));
+    assert_eq!(
+        store.get(&module_key(&module_id)),
+        Some(store_module().code().to_vec())
+    );
+    assert_eq!(store.get(&resource_key(&CORE_CODE_ADDRESS, &tag)), None);
+}
+
+#[test]
+fn test_master_of_coin_inconsistent_balance() {
+    struct Native(Balance);
+
+    impl NativeBalance for Native {
+        fn get_balance(&self, _: &WalletId) -> Option<Balance> {
+            Some(self.0)
+        }
+    }
+
+    let alice = AccountAddress::random();
+    let wallet = || {
+        WalletId::new(
+            alice,
+            StructTag {
+                address: CORE_CODE_ADDRESS,
+                module: Identifier::new("PONT").unwrap(),
+                name: Identifier::new("T").unwrap(),
+                type_params: vec![],
+            },
+        )
+    };
+
+    let mut moc = MasterOfCoin::new(Native(10));
+    moc.save_balance_operation(wallet(), BalanceOperation::Deposit(4));
+    moc.save_balance_operation(wallet(), BalanceOperation::Withdraw(4));
+    assert_eq!(moc.get_balance(&wallet()).unwrap(), Some(10));
+
+    // More coins deposited into the vm than the native wallet holds.
+    let mut moc = MasterOfCoin::new(Native(10));
+    moc.save_balance_operation(wallet(), BalanceOperation::Deposit(11));
+    assert_eq!(
+        moc.get_balance(&wallet()).unwrap_err().major_status(),
+        StatusCode::NATIVE_BALANCE_OPERATION_FAILED
+    );
+
+    // Withdrawals overflowing the native balance.
+    let mut moc = MasterOfCoin::new(Native(Balance::MAX));
+    moc.save_balance_operation(wallet(), BalanceOperation::Withdraw(1));
+    assert_eq!(
+        moc.get_balance(&wallet()).unwrap_err().major_status(),
+        StatusCode::NATIVE_BALANCE_OPERATION_FAILED
+    );
+}
+
+#[test]
+fn test_in_memory_bank_failed_operations() {
+    let bank = InMemoryBank::default();
+    let alice = AccountAddress::random();
+
+    // A failed deposit from a missing wallet does not open it.
+    assert_eq!(
+        bank.deposit(&alice, "PONT", 1),
+        Err(BalanceError::Unavailable)
+    );
+    assert_eq!(bank.get_balance(&alice, "PONT"), None);
+
+    bank.set_balance(&alice, "PONT", 10);
+    assert_eq!(
+        bank.deposit(&alice, "PONT", 11),
+        Err(BalanceError::InsufficientFunds)
+    );
+    assert_eq!(bank.get_balance(&alice, "PONT"), Some(10));
+    assert_eq!(
+        bank.withdraw(&alice, "PONT", Balance::MAX),
+        Err(BalanceError::Overflow)
+    );
+    assert_eq!(bank.get_balance(&alice, "PONT"), Some(10));
+
+    bank.deposit(&alice, "PONT", 4).unwrap();
+    assert_eq!(bank.get_balance(&alice, "PONT"), Some(6));
+
+    // Withdrawing into a missing wallet opens it.
+    bank.withdraw(&alice, "BTC", 3).unwrap();
+    assert_eq!(bank.get_balance(&alice, "BTC"), Some(3));
+}
@@ -0,0 +1,65 @@
+use move_core_types::account_address::AccountAddress;
+use move_core_types::identifier::Identifier;
+use move_core_types::language_storage::ModuleId;
+use mvm::dependencies::{topo_sort_modules, CycleError};
+use vm::file_format::{empty_module, IdentifierIndex, ModuleHandle};
+use vm::CompiledModule;
+
+fn module(name: &str, deps: &[&str]) -> CompiledModule {
+    let mut module = empty_module();
+    module.identifiers[0] = Identifier::new(name).unwrap();
+    for dep in deps {
+        module.identifiers.push(Identifier::new(*dep).unwrap());
+        module.module_handles.push(ModuleHandle {
+            address: module.module_handles[0].address,
+            name: IdentifierIndex(module.identifiers.len() as u16 - 1),
+        });
+    }
+    module.freeze().unwrap()
+}
+
+fn id(name: &str) -> ModuleId {
+    ModuleId::new(AccountAddress::ZERO, Identifier::new(name).unwrap())
+}
+
+#[test]
+fn test_sort_chain() {
+    let modules = vec![module("C", &["B"]), module("B", &["A"]), module("A", &[])];
+    assert_eq!(topo_sort_modules(&modules).unwrap(), vec![2, 1, 0]);
+
+    let modules = vec![module("A", &[]), module("B", &["A"]), module("C", &["B"])];
+    assert_eq!(topo_sort_modules(&modules).unwrap(), vec![0, 1, 2]);
+}
+
+#[test]
+fn test_sort_diamond() {
+    let modules = vec![
+        module("D", &["B", "C"]),
+        module("B", &["A"]),
+        module("C", &["A"]),
+        module("A", &[]),
+    ];
+    assert_eq!(topo_sort_modules(&modules).unwrap(), vec![3, 1, 2, 0]);
+}
+
+#[test]
+fn test_sort_external_dependency() {
+    let modules = vec![module("B", &["Signer"]), module("A", &["Signer"])];
+    assert_eq!(topo_sort_modules(&modules).unwrap(), vec![0, 1]);
+}
+
+#[test]
+fn test_sort_cycle() {
+    let modules = vec![
+        module("A", &[]),
+        module("B", &["C"]),
+        module("C", &["D"]),
+        module("D", &["B"]),
+    ];
+    assert_eq!(
+        topo_sort_modules(&modules).unwrap_err(),
+        CycleError {
+            modules: vec![id("B"), id("C"), id("D")],
+        }
+    );
+}
@@ -1,8 +1,12 @@
 use core::convert::TryFrom;
+use move_core_types::account_address::AccountAddress;
+use move_core_types::gas_schedule::{GasAlgebra, GasPrice, GasUnits};
 use move_core_types::identifier::Identifier;
 use move_core_types::language_storage::{StructTag, TypeTag, CORE_CODE_ADDRESS};
+use move_core_types::value::MoveValue;
 use move_vm_types::values::Value;
-use mvm::types::{parse_type_params, ModulePackage, Transaction};
+use mvm::types::{parse_type_params, Gas, GasError, ModulePackage, ScriptTxBuilder, Transaction};
+use mvm::vm_config::VmConfig;
 use vm::access::ModuleAccess;
 use vm::file_format::CompiledScript;
 use vm::CompiledModule;
@@ -130,3 +134,168 @@ fn test_parse_pac() {
         ]
     );
 }
+
+#[test]
+fn test_script_tx_builder() {
+    let code = include_bytes!("assets/target/scripts/store_u64.mv").to_vec();
+    let sender = AccountAddress::random();
+
+    let tx = ScriptTxBuilder::new(code.clone())
+        .unwrap()
+        .sender(sender)
+        .arg(MoveValue::U64(13))
+        .build()
+        .unwrap();
+    assert_eq!(tx.args(), &[Value::u64(13)][..]);
+    let (_, _, _, senders) = tx.into_inner();
+    assert_eq!(senders, vec![sender]);
+
+    let err = ScriptTxBuilder::new(code.clone())
+        .unwrap()
+        .sender(sender)
+        .arg(MoveValue::Address(sender))
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("Argument 0"));
+
+    assert!(ScriptTxBuilder::new(code.clone())
+        .unwrap()
+        .sender(sender)
+        .build()
+        .is_err());
+
+    let err = ScriptTxBuilder::new(code.clone())
+        .unwrap()
+        .sender(sender)
+        .sender(AccountAddress::random())
+        .arg(MoveValue::U64(13))
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("expects 1 signers, got 2"));
+
+    let err = ScriptTxBuilder::new(code)
+        .unwrap()
+        .sender(sender)
+        .arg(MoveValue::U64(13))
+        .type_arg(TypeTag::U8)
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("expects 0 type arguments, got 1"));
+}
+
+#[test]
+fn test_script_tx_builder_type_args() {
+    let code = include_bytes!("assets/target/scripts/register_coin.mv").to_vec();
+    let build = |type_args: Vec<TypeTag>, senders: Vec<AccountAddress>| {
+        let builder = ScriptTxBuilder::new(code.clone())
+            .unwrap()
+            .arg(MoveValue::Vector(vec![MoveValue::U8(1)]))
+            .arg(MoveValue::U8(2));
+        let builder = type_args
+            .into_iter()
+            .fold(builder, |builder, type_arg| builder.type_arg(type_arg));
+        senders
+            .into_iter()
+            .fold(builder, |builder, sender| builder.sender(sender))
+            .build()
+    };
+
+    let tx = build(vec![TypeTag::U64], vec![]).unwrap();
+    let (_, _, type_args, senders) = tx.into_inner();
+    assert_eq!(type_args, vec![TypeTag::U64]);
+    assert!(senders.is_empty());
+
+    assert!(build(vec![], vec![]).is_err());
+    assert!(build(vec![TypeTag::U64, TypeTag::U8], vec![]).is_err());
+    // The script takes no signers, so no senders are accepted either.
+    assert!(build(vec![TypeTag::U64], vec![AccountAddress::random()]).is_err());
+}
+
+#[test]
+fn test_gas_from_fee() {
+    let gas = Gas::from_fee(1_000, 10).unwrap();
+    assert_eq!(gas.max_gas_amount(), 100);
+    assert_eq!(gas.gas_unit_price(), 10);
+
+    let gas = Gas::from_fee(1_009, 10).unwrap();
+    assert_eq!(gas.max_gas_amount(), 100);
+
+    let gas = Gas::from_fee(9, 10).unwrap();
+    assert_eq!(gas.max_gas_amount(), 0);
+
+    assert_eq!(
+        Gas::from_fee(1_000, 0).unwrap_err(),
+        GasError::ZeroUnitPrice
+    );
+
+    let gas = Gas::from_fee(u128::MAX, 1).unwrap();
+    assert_eq!(gas.max_gas_amount(), 4_000_000);
+    assert_eq!(gas.gas_unit_price(), 1);
+
+    let gas = Gas::from_fee(u128::MAX, 10_000).unwrap();
+    assert_eq!(gas.max_gas_amount(), 4_000_000);
+
+    let gas = Gas::from_fee(5_000_000, 1).unwrap();
+    assert_eq!(gas.max_gas_amount(), 4_000_000);
+}
+
+#[test]
+fn test_gas_validation() {
+    let gas = Gas::new(10_000, 1).unwrap();
+    assert_eq!(gas.max_gas_amount(), 10_000);
+    assert_eq!(gas.gas_unit_price(), 1);
+    assert!(Gas::new(0, 1).is_ok());
+
+    assert_eq!(Gas::new(10_000, 0).unwrap_err(), GasError::ZeroUnitPrice);
+    assert_eq!(
+        Gas::new(u64::MAX, 1).unwrap_err(),
+        GasError::MaxGasAmountTooLarge {
+            amount: u64::MAX,
+            limit: 4_000_000
+        }
+    );
+    assert!(Gas::new(4_000_000, 1).is_ok());
+    assert_eq!(
+        Gas::new(4_000_001, 1).unwrap_err(),
+        GasError::MaxGasAmountTooLarge {
+            amount: 4_000_001,
+            limit: 4_000_000
+        }
+    );
+    assert!(Gas::new(10_000, 10_000).is_ok());
+    assert_eq!(
+        Gas::new(10_000, 10_001).unwrap_err(),
+        GasError::UnitPriceOutOfRange {
+            price: 10_001,
+            min: 0,
+            max: 10_000
+        }
+    );
+
+    let mut config = VmConfig::default();
+    let constants = &mut config.gas_schedule.gas_constants;
+    constants.maximum_number_of_gas_units = GasUnits::new(u64::MAX);
+    constants.min_price_per_gas_unit = GasPrice::new(5);
+    let limit = u64::MAX / 1000 - 1;
+    assert!(Gas::with_config(limit, 5, &config).is_ok());
+    assert_eq!(
+        Gas::with_config(limit + 1, 5, &config).unwrap_err(),
+        GasError::MaxGasAmountTooLarge {
+            amount: limit + 1,
+            limit
+        }
+    );
+    assert_eq!(
+        Gas::with_config(10_000, 4, &config).unwrap_err(),
+        GasError::UnitPriceOutOfRange {
+            price: 4,
+            min: 5,
+            max: 10_000
+        }
+    );
+    assert_eq!(
+        GasError::ZeroUnitPrice.to_string(),
+        "gas_unit_price must be greater than 0"
+    );
+    let _: &dyn std::error::Error = &GasError::ZeroUnitPrice;
+}
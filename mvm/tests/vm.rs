@@ -1,16 +1,40 @@
 #[macro_use]
 extern crate alloc;
 
+use std::cell::{Cell, RefCell};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
 use common::mock::Utils;
 use common::{assets::*, mock::*, vm};
 use move_core_types::account_address::AccountAddress;
+use move_core_types::gas_schedule::{AbstractMemorySize, GasAlgebra, GasUnits};
 use move_core_types::identifier::Identifier;
 use move_core_types::language_storage::{ModuleId, StructTag, TypeTag, CORE_CODE_ADDRESS};
+use move_core_types::value::MoveTypeLayout;
 use move_core_types::vm_status::StatusCode;
 use move_vm_runtime::data_cache::RemoteCache;
-use mvm::data::{BalanceAccess, ExecutionContext, State};
-use mvm::types::Gas;
+use move_vm_runtime::logging::NoContextLog;
+use move_vm_runtime::move_vm::MoveVM;
+use move_vm_types::gas_schedule::{calculate_intrinsic_gas, CostStrategy};
+use move_vm_types::natives::balance::Balance;
+use move_vm_types::values::Value;
+use mvm::data::{
+    module_key, resource_key, AccessKey, AddressPolicy, BalanceAccess, BalanceError, Bank,
+    BankConfig, ContextLogger, ExecutionContext, Resolve, State, StateBuilder, Storage,
+    TransferKind,
+};
+use mvm::gas_schedule::cost_table;
+use mvm::mvm::Mvm;
+use mvm::types::{
+    EffectToggles, FunctionTx, Gas, ModuleTx, OpcodePolicy, PublishPackageTx, RepublishPolicy,
+    ScriptArg,
+};
 use mvm::Vm;
+use vm::errors::{Location, PartialVMResult, VMError};
+use vm::file_format::CompiledModuleMut;
+use vm::file_format_common::Opcodes;
+use vm::CompiledModule;
 
 mod common;
 
@@ -29,6 +53,57 @@ fn test_public_module() {
     );
 }
 
+#[cfg(feature = "trusted_publish")]
+#[test]
+fn test_publish_module_unverified() {
+    let store_module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+
+    let (vm, store, _, oracle, _) = vm();
+    let verified = State::new(store, oracle);
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+
+    let (vm, store, _, oracle, _) = vm();
+    let unverified = State::new(store, oracle);
+    let res = vm.publish_module_unverified(gas(), store_module());
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+
+    assert_eq!(
+        verified.get_module(&store_module_id).unwrap(),
+        unverified.get_module(&store_module_id).unwrap()
+    );
+
+    let res = vm.publish_module_unverified(gas(), store_module());
+    assert_eq!(res.status_code, StatusCode::DUPLICATE_MODULE_NAME);
+}
+
+#[test]
+fn test_estimate_module_gas() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    let small = vm.estimate_module_gas(abort_module().code()).unwrap();
+    let large = vm.estimate_module_gas(store_module().code()).unwrap();
+    assert!(small > 0);
+    assert!(large > small);
+
+    let store_module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    assert!(state.get_module(&store_module_id).unwrap().is_none());
+
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert_eq!(res.gas_used, large);
+
+    let res = vm.publish_module(gas(), abort_module(), false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert_eq!(res.gas_used, small);
+    let breakdown = res.gas_breakdown;
+    assert_eq!(
+        small,
+        breakdown.intrinsic + breakdown.execution + breakdown.storage
+    );
+}
+
 #[test]
 fn test_public_module_without_gas() {
     let (vm, _, _, _, _) = vm();
@@ -62,6 +137,68 @@ fn test_execute_script() {
     assert_eq!(test_value, store.val);
 }
 
+#[test]
+fn test_execute_function() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    vm.pub_mod(store_module());
+
+    let res = vm.execute_function(
+        gas(),
+        ExecutionContext::new(100, 100),
+        FunctionTx::new(
+            ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap()),
+            Identifier::new("store_u64").unwrap(),
+            vec![ScriptArg::U64(13)],
+            vec![],
+            vec![addr("0x1")],
+        ),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert!(res.gas_used > 0);
+
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let blob = state
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .unwrap();
+    let store: StoreU64 = bcs::from_bytes(&blob).unwrap();
+    assert_eq!(store.val, 13);
+}
+
+#[test]
+fn test_resources_under() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    vm.pub_mod(store_module());
+    vm.exec(store_u64_script(addr("0x1"), 13));
+
+    let resources = state.resources_under(&CORE_CODE_ADDRESS);
+    assert_eq!(resources.len(), 1);
+    let (tag, blob) = &resources[0];
+    assert_eq!(
+        tag,
+        &StructTag {
+            address: CORE_CODE_ADDRESS,
+            module: Identifier::new("Store").unwrap(),
+            name: Identifier::new("U64").unwrap(),
+            type_params: vec![],
+        }
+    );
+    let store: StoreU64 = bcs::from_bytes(blob).unwrap();
+    assert_eq!(store.val, 13);
+
+    assert!(state.resources_under(&addr("0x2")).is_empty());
+}
+
 #[test]
 fn test_store_event() {
     let test_value = 13;
@@ -137,6 +274,92 @@ fn test_load_system_resources() {
     assert_eq!(store.val, timestamp);
 }
 
+struct StoreResolver(AccountAddress);
+
+impl Resolve for StoreResolver {
+    fn resolve(
+        &self,
+        address: &AccountAddress,
+        tag: &StructTag,
+    ) -> PartialVMResult<Option<Vec<u8>>> {
+        if address == &self.0 && tag.module.as_str() == "Store" && tag.name.as_str() == "U64" {
+            Ok(Some(bcs::to_bytes(&1u64).unwrap()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[test]
+fn test_with_resolver() {
+    let (vm, store, events, oracle, bank) = vm();
+    vm.pub_mod(store_module());
+
+    let vm = Mvm::new(store, events, oracle, bank)
+        .unwrap()
+        .with_resolver(StoreResolver(addr("0x1")));
+
+    // The resolver serves `Store::U64` under 0x1, so it can not be stored there again.
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::RESOURCE_ALREADY_EXISTS);
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x2"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+}
+
+#[test]
+fn test_access_set() {
+    let (vm, _, _, _, _) = vm();
+    vm.pub_mod(store_module());
+    vm.pub_mod(time_module());
+    vm.pub_mod(block_module());
+    vm.clear();
+
+    let (res, access) = vm.execute_script_with_access_set(
+        gas(),
+        ExecutionContext::new(10, 1000),
+        store_sys_resources_script(addr("0x1"), addr("0x2")),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+
+    let tag = |module: &str, name: &str| StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new(module).unwrap(),
+        name: Identifier::new(name).unwrap(),
+        type_params: vec![],
+    };
+    for module in &["Store", "Time", "Block"] {
+        let id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new(*module).unwrap());
+        assert!(access.reads.contains(&module_key(&id)));
+    }
+    assert!(access.reads.contains(&resource_key(
+        &CORE_CODE_ADDRESS,
+        &tag("Block", "BlockMetadata")
+    )));
+    assert!(access.reads.contains(&resource_key(
+        &CORE_CODE_ADDRESS,
+        &tag("Time", "CurrentTimestamp")
+    )));
+    assert_eq!(access.writes.len(), 2);
+    assert!(access
+        .writes
+        .contains(&resource_key(&addr("0x1"), &tag("Store", "U64"))));
+    assert!(access
+        .writes
+        .contains(&resource_key(&addr("0x2"), &tag("Store", "U64"))));
+}
+
 #[test]
 fn test_oracle() {
     let (vm, store, _, oracle, _) = vm();
@@ -190,6 +413,113 @@ fn test_error_event() {
     );
 }
 
+#[derive(Clone, Default)]
+struct CapturingLogger(Rc<RefCell<Vec<(AccountAddress, StatusCode)>>>);
+
+impl ContextLogger for CapturingLogger {
+    fn on_error(&self, sender: &AccountAddress, err: &VMError) {
+        self.0.borrow_mut().push((*sender, err.major_status()));
+    }
+}
+
+#[test]
+fn test_logger() {
+    let logger = CapturingLogger::default();
+    let (vm, _, _, _, _) = vm();
+    let vm = vm.with_logger(logger.clone());
+    vm.pub_mod(abort_module());
+    assert!(logger.0.borrow().is_empty());
+
+    let sender = AccountAddress::random();
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(0, 0),
+        error_script(sender),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::ABORTED);
+    assert_eq!(
+        logger.0.borrow().as_slice(),
+        &[(sender, StatusCode::ABORTED)]
+    );
+}
+
+#[cfg(feature = "serde-types")]
+#[test]
+fn test_vm_result_serde() {
+    use mvm::types::VmResult;
+
+    let (vm, _, _, _, _) = vm();
+    vm.pub_mod(abort_module());
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(0, 0),
+        error_script(AccountAddress::random()),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::ABORTED);
+
+    let blob = bcs::to_bytes(&res).unwrap();
+    assert_eq!(blob[..8], u64::from(StatusCode::ABORTED).to_le_bytes());
+    let decoded: VmResult = bcs::from_bytes(&blob).unwrap();
+    assert_eq!(decoded.status_code, res.status_code);
+    assert_eq!(decoded.sub_status, res.sub_status);
+    assert_eq!(decoded.gas_used, res.gas_used);
+    assert_eq!(decoded.gas_breakdown, res.gas_breakdown);
+    assert_eq!(decoded.error, res.error);
+
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(
+        json["status_code"],
+        serde_json::json!(u64::from(StatusCode::ABORTED))
+    );
+    assert_eq!(
+        json["error"]["major_status"],
+        serde_json::json!(u64::from(StatusCode::ABORTED))
+    );
+    assert!(json["error"]["location"].is_object());
+    let decoded: VmResult = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded.status_code, res.status_code);
+    assert_eq!(decoded.error, res.error);
+    assert!(matches!(
+        decoded.error.map(|err| err.location),
+        Some(Location::Module(_))
+    ));
+
+    let gas = gas();
+    let decoded: Gas = bcs::from_bytes(&bcs::to_bytes(&gas).unwrap()).unwrap();
+    assert_eq!(decoded.max_gas_amount(), gas.max_gas_amount());
+    assert_eq!(decoded.gas_unit_price(), gas.gas_unit_price());
+}
+
+#[test]
+fn test_error_details() {
+    let (vm, _, _, _, _) = vm();
+    vm.pub_mod(abort_module());
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(0, 0),
+        error_script(AccountAddress::random()),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::ABORTED);
+    assert_eq!(res.sub_status, Some(13));
+
+    let error = res.error.unwrap();
+    assert_eq!(error.major_status, StatusCode::ABORTED);
+    assert_eq!(error.sub_status, res.sub_status);
+    assert_eq!(
+        error.location,
+        Location::Module(ModuleId::new(
+            CORE_CODE_ADDRESS,
+            Identifier::new("Abort").unwrap()
+        ))
+    );
+
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert!(res.error.is_none());
+}
+
 #[test]
 fn test_publish_pac() {
     let (vm, state, _, oracle, _) = vm();
@@ -266,6 +596,168 @@ fn test_balance() {
     assert_eq!(bank.get_balance(&addr_2, "BTC"), None);
 }
 
+struct LockedBank(BankMock);
+
+impl BalanceAccess for LockedBank {
+    fn get_balance(&self, address: &AccountAddress, ticker: &str) -> Option<Balance> {
+        self.0.get_balance(address, ticker)
+    }
+
+    fn deposit(
+        &self,
+        _address: &AccountAddress,
+        _ticker: &str,
+        _amount: Balance,
+    ) -> Result<(), BalanceError> {
+        Err(BalanceError::Unavailable)
+    }
+
+    fn withdraw(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError> {
+        self.0.withdraw(address, ticker, amount)
+    }
+}
+
+#[test]
+fn test_locked_balance() {
+    let bank = BankMock::default();
+    let vm = Mvm::new(
+        StorageMock::new(),
+        EventHandlerMock::default(),
+        OracleMock::default(),
+        LockedBank(bank.clone()),
+    )
+    .unwrap();
+    vm.pub_mod(coins_module());
+    vm.pub_mod(pont_module());
+    vm.pub_mod(signer_module());
+    vm.pub_mod(event_module());
+    vm.pub_mod(pontem_module());
+    vm.pub_mod(account_module());
+
+    let addr_1 = AccountAddress::random();
+    let addr_2 = AccountAddress::random();
+    bank.set_balance(&addr_1, "USDT", 1024);
+    bank.set_balance(&addr_1, "PONT", 64);
+    bank.set_balance(&addr_1, "BTC", 13);
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        test_balance_script(addr_1, addr_2, 1024, 64, 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::NATIVE_BALANCE_OPERATION_FAILED);
+    assert_eq!(bank.get_balance(&addr_1, "USDT"), Some(1024));
+    assert_eq!(bank.get_balance(&addr_1, "PONT"), Some(64));
+}
+
+/// Bank failing the `fail_at`-th balance change it is asked for.
+struct FailingBank {
+    bank: BankMock,
+    calls: Cell<usize>,
+    fail_at: usize,
+}
+
+impl FailingBank {
+    fn call(&self) -> Result<(), BalanceError> {
+        self.calls.set(self.calls.get() + 1);
+        if self.calls.get() == self.fail_at {
+            Err(BalanceError::Unavailable)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl BalanceAccess for FailingBank {
+    fn get_balance(&self, address: &AccountAddress, ticker: &str) -> Option<Balance> {
+        self.bank.get_balance(address, ticker)
+    }
+
+    fn deposit(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError> {
+        self.call()?;
+        self.bank.deposit(address, ticker, amount)
+    }
+
+    fn withdraw(
+        &self,
+        address: &AccountAddress,
+        ticker: &str,
+        amount: Balance,
+    ) -> Result<(), BalanceError> {
+        self.call()?;
+        self.bank.withdraw(address, ticker, amount)
+    }
+}
+
+#[test]
+fn test_failed_balance_operation_leaves_no_effects() {
+    let (vm, store, events, oracle, bank) = vm();
+    vm.pub_mod(coins_module());
+    vm.pub_mod(pont_module());
+    vm.pub_mod(signer_module());
+    vm.pub_mod(event_module());
+    vm.pub_mod(pontem_module());
+    vm.pub_mod(account_module());
+    vm.pub_mod(event_proxy_module());
+    vm.pub_mod(store_module());
+
+    let alice = AccountAddress::random();
+    let bob = AccountAddress::random();
+    bank.set_balance(&alice, "USDT", 1024);
+    bank.set_balance(&bob, "USDT", 7);
+
+    let state = State::new(store.clone(), oracle.clone());
+    let snapshot = state.snapshot();
+    events.data.borrow_mut().clear();
+
+    // The script changes two wallets, the second balance change fails.
+    let vm = Mvm::new(
+        store,
+        events.clone(),
+        oracle,
+        FailingBank {
+            bank: bank.clone(),
+            calls: Cell::new(0),
+            fail_at: 2,
+        },
+    )
+    .unwrap();
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_emit_transfer_script(alice, bob, 512),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::NATIVE_BALANCE_OPERATION_FAILED);
+
+    assert_eq!(state.snapshot(), snapshot);
+    assert_eq!(bank.get_balance(&alice, "USDT"), Some(1024));
+    assert_eq!(bank.get_balance(&bob, "USDT"), Some(7));
+    // Only the status of the failed transaction reaches the handler.
+    assert_eq!(events.data.borrow().len(), 1);
+    let (_, tag, _, _) = events.pop().unwrap();
+    assert_eq!(
+        tag,
+        TypeTag::Struct(StructTag {
+            address: CORE_CODE_ADDRESS,
+            module: Identifier::new("VMStatus").unwrap(),
+            name: Identifier::new("VMStatus").unwrap(),
+            type_params: vec![],
+        })
+    );
+}
+
 #[test]
 fn test_transfer() {
     let (vm, store, _, oracle, bank) = vm();
@@ -328,3 +820,1200 @@ fn test_transfer() {
 
     assert_eq!(bob_account, send_to_bob);
 }
+
+#[test]
+fn test_transfer_insufficient_funds() {
+    let (vm, _, _, _, bank) = vm();
+    vm.pub_mod(coins_module());
+    vm.pub_mod(pont_module());
+    vm.pub_mod(signer_module());
+    vm.pub_mod(event_module());
+    vm.pub_mod(pontem_module());
+    vm.pub_mod(account_module());
+    vm.exec(reg_coin_script(
+        TypeTag::Struct(StructTag {
+            address: CORE_CODE_ADDRESS,
+            module: Identifier::new("PONT").unwrap(),
+            name: Identifier::new("T").unwrap(),
+            type_params: vec![],
+        }),
+        "PONT",
+        2,
+    ));
+
+    let alice = AccountAddress::random();
+    bank.set_balance(&alice, "PONT", 100);
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        test_transfer_script(alice, AccountAddress::random(), 101),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::ABORTED);
+    assert_eq!(bank.get_balance(&alice, "PONT"), Some(100));
+
+    assert_eq!(
+        bank.deposit(&alice, "PONT", 101),
+        Err(BalanceError::InsufficientFunds)
+    );
+    assert_eq!(
+        bank.withdraw(&alice, "PONT", Balance::MAX),
+        Err(BalanceError::Overflow)
+    );
+    assert_eq!(bank.get_balance(&alice, "PONT"), Some(100));
+}
+
+#[test]
+fn test_system_script() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    vm.pub_mod(coins_module());
+    vm.pub_mod(pont_module());
+    vm.pub_mod(signer_module());
+    vm.pub_mod(event_module());
+    vm.pub_mod(pontem_module());
+
+    let pont = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("PONT").unwrap(),
+        name: Identifier::new("T").unwrap(),
+        type_params: vec![],
+    };
+    vm.exec(reg_coin_script(TypeTag::Struct(pont.clone()), "PONT", 2));
+
+    let info = state
+        .get_resource(
+            &CORE_CODE_ADDRESS,
+            &StructTag {
+                address: CORE_CODE_ADDRESS,
+                module: Identifier::new("Pontem").unwrap(),
+                name: Identifier::new("Info").unwrap(),
+                type_params: vec![TypeTag::Struct(pont)],
+            },
+        )
+        .unwrap();
+    assert!(info.is_some());
+}
+
+#[test]
+fn test_balance_operation_gas() {
+    let transfer = |operation_gas| {
+        let (vm, _, _, _, bank) = vm();
+        let vm = vm.with_bank_config(BankConfig {
+            operation_gas,
+            ..BankConfig::pontem()
+        });
+        vm.pub_mod(coins_module());
+        vm.pub_mod(pont_module());
+        vm.pub_mod(signer_module());
+        vm.pub_mod(event_module());
+        vm.pub_mod(pontem_module());
+        vm.pub_mod(account_module());
+        vm.exec(reg_coin_script(
+            TypeTag::Struct(StructTag {
+                address: CORE_CODE_ADDRESS,
+                module: Identifier::new("PONT").unwrap(),
+                name: Identifier::new("T").unwrap(),
+                type_params: vec![],
+            }),
+            "PONT",
+            2,
+        ));
+
+        let alice = AccountAddress::random();
+        bank.set_balance(&alice, "PONT", 100);
+        let res = vm.execute_script(
+            gas(),
+            ExecutionContext::new(100, 100),
+            test_transfer_script(alice, AccountAddress::random(), 4),
+            false,
+        );
+        assert_eq!(res.status_code, StatusCode::EXECUTED);
+        res.gas_used
+    };
+
+    let free = transfer(0);
+    let charged = transfer(100);
+    assert!(charged > free);
+    assert_eq!((charged - free) % 100, 0);
+}
+
+#[test]
+fn test_balance_operation_gas_counts_every_operation() {
+    let balance_script = |operation_gas| {
+        let (vm, _, _, _, bank) = vm();
+        let vm = vm.with_bank_config(BankConfig {
+            operation_gas,
+            ..BankConfig::pontem()
+        });
+        vm.pub_mod(coins_module());
+        vm.pub_mod(pont_module());
+        vm.pub_mod(signer_module());
+        vm.pub_mod(event_module());
+        vm.pub_mod(pontem_module());
+        vm.pub_mod(account_module());
+
+        let addr_1 = AccountAddress::random();
+        let addr_2 = AccountAddress::random();
+        bank.set_balance(&addr_1, "USDT", 1024);
+        bank.set_balance(&addr_1, "PONT", 64);
+        bank.set_balance(&addr_1, "BTC", 13);
+        let res = vm.execute_script(
+            Gas::new(100_000, 1).unwrap(),
+            ExecutionContext::new(100, 100),
+            test_balance_script(addr_1, addr_2, 1024, 64, 13),
+            false,
+        );
+        assert_eq!(res.status_code, StatusCode::EXECUTED);
+        res.gas_used
+    };
+
+    // The script makes 7 balance operations on 4 wallets.
+    let free = balance_script(0);
+    let charged = balance_script(100);
+    assert_eq!(charged - free, 7 * 100);
+}
+
+#[test]
+fn test_apply_resources_only() {
+    let (vm, store, events, oracle, bank) = vm();
+    vm.pub_mod(store_module());
+    vm.pub_mod(event_module());
+    vm.pub_mod(event_proxy_module());
+
+    let vm = Mvm::new(store.clone(), events.clone(), oracle.clone(), bank)
+        .unwrap()
+        .with_effect_toggles(EffectToggles::resources_only());
+    let state = State::new(store, oracle);
+
+    vm.exec(store_u64_script(addr("0x1"), 13));
+    vm.exec(emit_event_script(addr("0x1"), 13));
+    vm.pub_mod(abort_module());
+
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let blob = state
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .unwrap();
+    assert_eq!(13, bcs::from_bytes::<StoreU64>(&blob).unwrap().val);
+
+    assert!(events.data.borrow().is_empty());
+    assert!(state
+        .get_module(&ModuleId::new(
+            CORE_CODE_ADDRESS,
+            Identifier::new("Abort").unwrap()
+        ))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_apply_resources_layout_mismatch() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    let err = vm
+        .apply_resources(vec![(
+            addr("0x1"),
+            vec![(tag.clone(), Some((MoveTypeLayout::U64, Value::bool(true))))],
+        )])
+        .unwrap_err();
+    assert_eq!(err.major_status(), StatusCode::VALUE_SERIALIZATION_ERROR);
+    assert!(err.message().unwrap().contains("::Store::U64"));
+    assert!(state.get_resource(&addr("0x1"), &tag).unwrap().is_none());
+}
+
+#[test]
+fn test_publish_module_bundle_ordered() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    let res = vm.publish_module_bundle_ordered(gas(), vec![event_module(), event_proxy_module()]);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert!(state
+        .get_module(&ModuleId::new(
+            CORE_CODE_ADDRESS,
+            Identifier::new("EventProxy").unwrap()
+        ))
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn test_publish_module_bundle_wrong_order() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    let res = vm.publish_module_bundle_ordered(gas(), vec![event_proxy_module(), event_module()]);
+    assert_eq!(res.status_code, StatusCode::MISSING_DEPENDENCY);
+    assert!(state
+        .get_module(&ModuleId::new(
+            CORE_CODE_ADDRESS,
+            Identifier::new("Event").unwrap()
+        ))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_publish_package_of_module_txs() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    let package =
+        PublishPackageTx::try_from(vec![event_proxy_module(), event_module(), store_module()])
+            .unwrap();
+    let res = vm.publish_module_package(gas(), package, false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+
+    for name in &["Event", "EventProxy", "Store"] {
+        assert!(state
+            .get_module(&ModuleId::new(
+                CORE_CODE_ADDRESS,
+                Identifier::new(*name).unwrap()
+            ))
+            .unwrap()
+            .is_some());
+    }
+}
+
+#[test]
+fn test_package_with_different_senders() {
+    let module = ModuleTx::new(store_module().code().to_vec(), addr("0x2"));
+    assert!(PublishPackageTx::try_from(vec![event_module(), module]).is_err());
+}
+
+#[test]
+fn test_remaining_gas_after_execution() {
+    let (vm, _, _, _, _) = vm();
+    vm.pub_mod(store_module());
+
+    let gas = gas();
+    let max_gas_amount = gas.max_gas_amount();
+    let res = vm.execute_script(
+        gas,
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+
+    let execution_gas = max_gas_amount - res.remaining_gas_after_execution;
+    assert!(execution_gas > 0);
+    assert!(res.gas_used > execution_gas);
+    assert_eq!(
+        res.gas_used - execution_gas,
+        res.gas_breakdown.storage + res.gas_breakdown.events
+    );
+
+    // Publishing captures the gas left before the storage charges as well.
+    let (vm, _, _, _, _) = vm();
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    let execution_gas = max_gas_amount - res.remaining_gas_after_execution;
+    assert_eq!(
+        execution_gas,
+        res.gas_breakdown.intrinsic + res.gas_breakdown.execution
+    );
+    assert!(res.gas_breakdown.storage > 0);
+    assert_eq!(res.gas_used - execution_gas, res.gas_breakdown.storage);
+}
+
+#[test]
+fn test_refund() {
+    let (vm, _, _, _, _) = vm();
+    vm.pub_mod(store_module());
+
+    let gas = Gas::from_fee(100_005, 10).unwrap();
+    let res = vm.execute_script(
+        gas,
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert!(res.gas_used > 0);
+    assert_eq!(res.refund, (10_000 - res.gas_used) as u128 * 10);
+}
+
+#[test]
+fn test_dry_run_script() {
+    let (vm, store, events, oracle, _) = vm();
+    let state = State::new(store, oracle);
+    vm.pub_mod(store_module());
+    vm.pub_mod(event_module());
+    vm.pub_mod(event_proxy_module());
+
+    let dry_run = vm.dry_run_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+    );
+    assert_eq!(dry_run.status_code, StatusCode::EXECUTED);
+
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    assert!(state
+        .get_resource(&CORE_CODE_ADDRESS, &tag)
+        .unwrap()
+        .is_none());
+
+    let res = vm.dry_run_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        emit_event_script(addr("0x1"), 13),
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert!(events.data.borrow().is_empty());
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.gas_used, dry_run.gas_used);
+}
+
+#[test]
+fn test_execute_script_for_writeset() {
+    let (vm, store, _, _, _) = vm();
+    vm.pub_mod(store_module());
+    vm.pub_mod(abort_module());
+
+    let (res, writes) = vm.execute_script_for_writeset(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert_eq!(writes.len(), 1);
+    for (key, _) in &writes {
+        assert!(store.get(key).is_none());
+    }
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    for (key, value) in writes {
+        assert_eq!(store.get(&key), value);
+    }
+
+    let (res, writes) = vm.execute_script_for_writeset(
+        gas(),
+        ExecutionContext::new(100, 100),
+        error_script(addr("0x1")),
+    );
+    assert_ne!(res.status_code, StatusCode::EXECUTED);
+    assert!(writes.is_empty());
+}
+
+#[test]
+fn test_republish_rejected() {
+    let (vm, store, event, oracle, bank) = vm();
+    vm.pub_mod(store_module());
+
+    let vm = Mvm::new(store, event, oracle, bank)
+        .unwrap()
+        .with_republish_policy(RepublishPolicy::Reject);
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert_eq!(res.status_code, StatusCode::DUPLICATE_MODULE_NAME);
+}
+
+#[test]
+fn test_republish_compatible() {
+    let (vm, _, _, _, _) = vm();
+    vm.pub_mod(store_module());
+
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+
+    vm.exec(store_u64_script(addr("0x1"), 13));
+}
+
+#[test]
+fn test_republish_any() {
+    let (vm, store, event, oracle, bank) = vm();
+    vm.pub_mod(store_module());
+
+    let vm = Mvm::new(store, event, oracle, bank)
+        .unwrap()
+        .with_republish_policy(RepublishPolicy::AllowAny);
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+}
+
+#[test]
+fn test_events_in_result() {
+    let (vm, _, events, _, _) = vm();
+    vm.pub_mod(event_module());
+    vm.pub_mod(event_proxy_module());
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        emit_event_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert_eq!(res.events.len(), 2);
+    assert_eq!(res.events, *events.data.borrow());
+}
+
+#[test]
+fn test_event_order() {
+    let (vm, _, events, _, _) = vm();
+    vm.pub_mod(event_module());
+    vm.pub_mod(event_proxy_module());
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        emit_events_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert_eq!(res.events, *events.data.borrow());
+
+    let values = events
+        .data
+        .borrow()
+        .iter()
+        .map(|(_, _, msg, _)| bcs::from_bytes::<StoreU64>(msg).unwrap().val)
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec![13, 14, 15]);
+}
+
+#[test]
+fn test_gas_breakdown() {
+    let (vm, _, _, _, _) = vm();
+
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    let breakdown = res.gas_breakdown;
+    assert!(breakdown.intrinsic > 0);
+    assert!(breakdown.storage > 0);
+    assert_eq!(
+        breakdown.intrinsic + breakdown.execution + breakdown.storage,
+        res.gas_used
+    );
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    let breakdown = res.gas_breakdown;
+    assert_eq!(breakdown.intrinsic, 0);
+    assert!(breakdown.execution > 0);
+    assert!(breakdown.storage > 0);
+    assert_eq!(breakdown.execution + breakdown.storage, res.gas_used);
+}
+
+#[test]
+fn test_inconsistent_module_identity() {
+    let (vm, _, _, _, _) = vm();
+    let serialize = |module: CompiledModuleMut| {
+        let mut code = vec![];
+        module.serialize(&mut code).unwrap();
+        ModuleTx::new(code, CORE_CODE_ADDRESS)
+    };
+    let store = || {
+        CompiledModule::deserialize(store_module().code())
+            .unwrap()
+            .into_inner()
+    };
+
+    // A second handle referring to the module itself.
+    let mut module = store();
+    let self_handle = module.module_handles[module.self_module_handle_idx.0 as usize].clone();
+    module.module_handles.push(self_handle);
+    let res = vm.publish_module(gas(), serialize(module), false);
+    assert_eq!(res.status_code, StatusCode::DUPLICATE_ELEMENT);
+
+    // The module claims an address other than the one it is published under.
+    let mut module = store();
+    let address = module.module_handles[module.self_module_handle_idx.0 as usize].address;
+    module.address_identifiers[address.0 as usize] = AccountAddress::random();
+    let res = vm.publish_module(gas(), serialize(module), false);
+    assert_eq!(
+        res.status_code,
+        StatusCode::MODULE_ADDRESS_DOES_NOT_MATCH_SENDER
+    );
+}
+
+#[test]
+fn test_validate_module() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+    let module = store_module();
+    let module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+
+    vm.validate_module(module.code()).unwrap();
+    assert!(state.get_module(&module_id).unwrap().is_none());
+
+    let truncated = &module.code()[..module.code().len() / 2];
+    let err = vm.validate_module(truncated).unwrap_err();
+    assert_eq!(err.location(), &Location::Undefined);
+    assert_ne!(err.major_status(), StatusCode::EXECUTED);
+}
+
+#[test]
+fn test_out_of_gas() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+    vm.pub_mod(store_module());
+
+    let max_gas_amount = 1;
+    let res = vm.execute_script(
+        Gas::new(max_gas_amount, 1).unwrap(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::OUT_OF_GAS);
+    assert_eq!(res.gas_used, max_gas_amount);
+    assert_eq!(res.remaining_gas_after_execution, 0);
+
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    assert!(state.get_resource(&addr("0x1"), &tag).unwrap().is_none());
+}
+
+#[test]
+fn test_publish_module_single_intrinsic_charge() {
+    let (vm, _, _, _, _) = vm();
+    let module = store_module();
+    let intrinsic = calculate_intrinsic_gas(
+        AbstractMemorySize::new(module.code().len() as u64),
+        &cost_table().gas_constants,
+    )
+    .get();
+
+    let res = vm.publish_module(gas(), module, false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert_eq!(res.gas_breakdown.intrinsic, intrinsic);
+    assert_eq!(
+        res.gas_used,
+        intrinsic + res.gas_breakdown.execution + res.gas_breakdown.storage
+    );
+}
+
+struct ForbidCore;
+
+impl AddressPolicy for ForbidCore {
+    fn allow_write(&self, address: &AccountAddress) -> bool {
+        address != &CORE_CODE_ADDRESS
+    }
+}
+
+#[test]
+fn test_address_policy() {
+    let (vm, store, _, oracle, _) = vm();
+    let vm = vm.with_address_policy(ForbidCore);
+    let state = State::new(store, oracle);
+
+    vm.pub_mod(store_module());
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::WRITE_TO_FORBIDDEN_ADDRESS);
+    assert!(state.resources_under(&CORE_CODE_ADDRESS).is_empty());
+}
+
+#[test]
+fn test_opcode_policy() {
+    let (vm, store, _, oracle, _) = vm();
+    let vm = vm.with_opcode_policy(OpcodePolicy::allow_all().forbid(Opcodes::MOVE_TO));
+    let state = State::new(store, oracle);
+
+    let res = vm.publish_module(gas(), store_module(), false);
+    assert_eq!(res.status_code, StatusCode::FORBIDDEN_OPCODE);
+    let id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    assert!(state.get_module(&id).unwrap().is_none());
+
+    let (vm, store, _, oracle, _) = common::vm();
+    let state = State::new(store, oracle);
+    vm.pub_mod(store_module());
+    let vm = vm.with_opcode_policy(OpcodePolicy::allow_all().forbid(Opcodes::CALL));
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::FORBIDDEN_OPCODE);
+    assert!(state.resources_under(&CORE_CODE_ADDRESS).is_empty());
+}
+
+#[test]
+fn test_event_filter() {
+    let (vm, _, events, _, _) = vm();
+    vm.pub_mod(event_module());
+    vm.pub_mod(event_proxy_module());
+    vm.pub_mod(abort_module());
+    let vm = vm.with_event_filter(|tag| match tag {
+        TypeTag::Struct(tag) => tag.module.as_str() == "VMStatus",
+        _ => false,
+    });
+
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        emit_event_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert_eq!(res.events.len(), 2);
+    assert!(events.data.borrow().is_empty());
+
+    vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        error_script(addr("0x1")),
+        false,
+    );
+    let (_, tag, _, _) = events.pop().unwrap();
+    assert_eq!(
+        tag,
+        TypeTag::Struct(StructTag {
+            address: CORE_CODE_ADDRESS,
+            module: Identifier::new("VMStatus").unwrap(),
+            name: Identifier::new("VMStatus").unwrap(),
+            type_params: vec![],
+        })
+    );
+    assert!(events.data.borrow().is_empty());
+}
+
+#[derive(Clone, Default)]
+struct LogStorage {
+    inner: StorageMock,
+    reads: Rc<RefCell<Vec<Vec<u8>>>>,
+    writes: Rc<RefCell<Vec<(Vec<u8>, Vec<u8>)>>>,
+}
+
+impl Storage for LogStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.reads.borrow_mut().push(key.to_vec());
+        self.inner.get(key)
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) {
+        self.writes
+            .borrow_mut()
+            .push((key.to_vec(), value.to_vec()));
+        self.inner.insert(key, value);
+    }
+
+    fn remove(&self, key: &[u8]) {
+        self.inner.remove(key);
+    }
+}
+
+#[test]
+fn test_clear_module() {
+    let store = LogStorage::default();
+    let vm = Mvm::new(
+        store.clone(),
+        EventHandlerMock::default(),
+        OracleMock::default(),
+        BankMock::default(),
+    )
+    .unwrap();
+    vm.pub_mod(store_module());
+    vm.pub_mod(abort_module());
+
+    let store_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let abort_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Abort").unwrap());
+    let module_reads = |id: &ModuleId| {
+        let key = AccessKey::from(id);
+        store
+            .reads
+            .borrow()
+            .iter()
+            .filter(|read| read.as_slice() == key.as_ref())
+            .count()
+    };
+    let run = |script| {
+        vm.execute_script(gas(), ExecutionContext::new(100, 100), script, true);
+    };
+
+    run(store_u64_script(addr("0x1"), 13));
+    run(error_script(addr("0x1")));
+    store.reads.borrow_mut().clear();
+
+    vm.clear_module(&store_id);
+    run(store_u64_script(addr("0x1"), 13));
+    run(error_script(addr("0x1")));
+    assert_eq!(module_reads(&store_id), 1);
+    assert_eq!(module_reads(&abort_id), 0);
+}
+
+#[test]
+fn test_preload_stdlib() {
+    let store = LogStorage::default();
+    let vm = Mvm::new(
+        store.clone(),
+        EventHandlerMock::default(),
+        OracleMock::default(),
+        BankMock::default(),
+    )
+    .unwrap();
+    vm.preload_stdlib(&[store_module()]).unwrap();
+    // Preloading is idempotent.
+    vm.preload_stdlib(&[store_module()]).unwrap();
+
+    let store_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let key = AccessKey::from(&store_id);
+    assert!(store.inner.get(key.as_ref()).is_some());
+
+    vm.clear();
+    store.reads.borrow_mut().clear();
+    vm.exec(store_u64_script(addr("0x1"), 13));
+    let module_reads = store
+        .reads
+        .borrow()
+        .iter()
+        .filter(|read| read.as_slice() == key.as_ref())
+        .count();
+    assert_eq!(module_reads, 0);
+}
+
+#[test]
+fn test_deterministic_writes() {
+    let run = || {
+        let store = LogStorage::default();
+        let vm = Mvm::new(
+            store.clone(),
+            EventHandlerMock::default(),
+            OracleMock::default(),
+            BankMock::default(),
+        )
+        .unwrap();
+        vm.pub_mod(store_module());
+        vm.pub_mod(time_module());
+        vm.pub_mod(block_module());
+        store.writes.borrow_mut().clear();
+
+        vm.exec_with_context(
+            ExecutionContext::new(10, 1000),
+            store_sys_resources_script(addr("0x2"), addr("0x1")),
+        );
+        let writes = store.writes.borrow().clone();
+        writes
+    };
+
+    let writes = run();
+    assert_eq!(writes.len(), 2);
+    assert!(writes[0].0 < writes[1].0);
+    assert_eq!(writes, run());
+}
+
+#[test]
+fn test_session_resource_before_image() {
+    let tag = |name: &str| StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new(name).unwrap(),
+        type_params: vec![],
+    };
+    let account = AccountAddress::random();
+    let module_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+    let state = StateBuilder::new()
+        .module(&module_id, store_module().code().to_vec())
+        .resource(&account, &tag("U128"), bcs::to_bytes(&13u128).unwrap())
+        .build();
+    let bank = Bank::new(BankMock::default(), BankConfig::default());
+
+    let move_vm = MoveVM::new();
+    let mut session = move_vm.new_session(&state, &bank);
+    let cost_table = cost_table();
+    let mut cost_strategy = CostStrategy::system(&cost_table, GasUnits::new(0));
+    let (script, args, type_args, senders) = store_u64_script(account, 5).into_inner();
+    session
+        .execute_script(
+            script,
+            type_args,
+            args,
+            senders,
+            &mut cost_strategy,
+            &NoContextLog::new(),
+        )
+        .unwrap();
+
+    // The session sees the stored value, but not its own writes.
+    assert_eq!(
+        session.get_resource_blob(&account, &tag("U128")).unwrap(),
+        Some(bcs::to_bytes(&13u128).unwrap())
+    );
+    assert_eq!(
+        session.get_resource_blob(&account, &tag("U64")).unwrap(),
+        None
+    );
+
+    let effects = session.finish().unwrap();
+    let (_, resources) = effects
+        .resources
+        .iter()
+        .find(|(addr, _)| addr == &account)
+        .unwrap();
+    let (_, value) = resources
+        .iter()
+        .find(|(resource, _)| resource == &tag("U64"))
+        .unwrap();
+    let (layout, value) = value.as_ref().unwrap();
+    assert_eq!(
+        value.simple_serialize(layout).unwrap(),
+        bcs::to_bytes(&5u64).unwrap()
+    );
+}
+
+#[test]
+fn test_remove_module() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+    vm.pub_mod(event_module());
+    vm.pub_mod(event_proxy_module());
+    let event_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Event").unwrap());
+    let proxy_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("EventProxy").unwrap());
+
+    // EventProxy depends on Event.
+    let res = vm.remove_module(gas(), event_id.clone(), CORE_CODE_ADDRESS);
+    assert_eq!(res.status_code, StatusCode::MODULE_IN_USE);
+    assert!(state.get_module(&event_id).unwrap().is_some());
+
+    let res = vm.remove_module(gas(), proxy_id.clone(), addr("0x2"));
+    assert_eq!(
+        res.status_code,
+        StatusCode::MODULE_ADDRESS_DOES_NOT_MATCH_SENDER
+    );
+
+    let proxy_size = state.get_module(&proxy_id).unwrap().unwrap().len();
+    let res = vm.remove_module(gas(), proxy_id.clone(), CORE_CODE_ADDRESS);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert!(state.get_module(&proxy_id).unwrap().is_none());
+    assert_eq!(
+        res.gas_breakdown.intrinsic,
+        calculate_intrinsic_gas(
+            AbstractMemorySize::new(proxy_size as u64),
+            &cost_table().gas_constants,
+        )
+        .get()
+    );
+
+    let res = vm.remove_module(gas(), event_id.clone(), CORE_CODE_ADDRESS);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert!(state.get_module(&event_id).unwrap().is_none());
+}
+
+#[test]
+fn test_remove_module_with_resources() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+    vm.pub_mod(store_module());
+    vm.exec(store_u64_script(addr("0x1"), 13));
+    let store_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Store").unwrap());
+
+    let res = vm.remove_module(gas(), store_id.clone(), CORE_CODE_ADDRESS);
+    assert_eq!(res.status_code, StatusCode::MODULE_IN_USE);
+    assert!(state.get_module(&store_id).unwrap().is_some());
+}
+
+#[test]
+fn test_remove_module_with_nested_vector_resource() {
+    let (vm, store, _, oracle, _) = vm();
+    vm.pub_mod(event_module());
+    let event_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Event").unwrap());
+
+    // A resource of another module instantiated with `vector<vector<Event::EventHandle>>`.
+    let handle = TypeTag::Struct(StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Event").unwrap(),
+        name: Identifier::new("EventHandle").unwrap(),
+        type_params: vec![],
+    });
+    let tag = StructTag {
+        address: addr("0x2"),
+        module: Identifier::new("Box").unwrap(),
+        name: Identifier::new("T").unwrap(),
+        type_params: vec![TypeTag::Vector(Box::new(TypeTag::Vector(Box::new(handle))))],
+    };
+    store.insert(&resource_key(&addr("0x3"), &tag), &[0]);
+
+    let res = vm.remove_module(gas(), event_id.clone(), CORE_CODE_ADDRESS);
+    assert_eq!(res.status_code, StatusCode::MODULE_IN_USE);
+
+    store.remove(&resource_key(&addr("0x3"), &tag));
+    let res = vm.remove_module(gas(), event_id.clone(), CORE_CODE_ADDRESS);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert!(State::new(store, oracle)
+        .get_module(&event_id)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_remove_module_charges_scanned_keys() {
+    let (vm, store, _, oracle, _) = vm();
+    vm.pub_mod(event_module());
+    let event_id = ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Event").unwrap());
+
+    // Resources of an unrelated module still have to be scanned.
+    let tag = StructTag {
+        address: addr("0x2"),
+        module: Identifier::new("Box").unwrap(),
+        name: Identifier::new("T").unwrap(),
+        type_params: vec![],
+    };
+    for holder in 0..1_000 {
+        store.insert(
+            &resource_key(&addr(&format!("0x{:x}", holder + 3)), &tag),
+            &[0],
+        );
+    }
+
+    let res = vm.remove_module(
+        Gas::new(100, 1).unwrap(),
+        event_id.clone(),
+        CORE_CODE_ADDRESS,
+    );
+    assert_eq!(res.status_code, StatusCode::OUT_OF_GAS);
+    let state = State::new(store, oracle);
+    assert!(state.get_module(&event_id).unwrap().is_some());
+
+    let res = vm.remove_module(gas(), event_id.clone(), CORE_CODE_ADDRESS);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+    assert!(res.gas_breakdown.execution > 100);
+    assert!(state.get_module(&event_id).unwrap().is_none());
+}
+
+#[test]
+fn test_missing_module_reported() {
+    let (vm, _, _, _, _) = vm();
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::LINKER_ERROR);
+    assert_eq!(
+        res.error.unwrap().missing_module,
+        Some(ModuleId::new(
+            CORE_CODE_ADDRESS,
+            Identifier::new("Store").unwrap()
+        ))
+    );
+}
+
+#[test]
+fn test_event_byte_gas() {
+    let emit = |event_byte_gas, msg: Vec<u8>| {
+        let (vm, _, _, _, _) = vm();
+        let vm = vm.with_event_byte_gas(event_byte_gas);
+        vm.pub_mod(event_module());
+        let res = vm.execute_function(
+            gas(),
+            ExecutionContext::new(100, 100),
+            FunctionTx::new(
+                ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Event").unwrap()),
+                Identifier::new("emit").unwrap(),
+                vec![ScriptArg::VectorU8(msg)],
+                vec![TypeTag::Vector(Box::new(TypeTag::U8))],
+                vec![addr("0x1")],
+            ),
+            false,
+        );
+        assert_eq!(res.status_code, StatusCode::EXECUTED);
+        res
+    };
+
+    let free = emit(0, vec![7; 500]);
+    assert_eq!(free.gas_breakdown.events, 0);
+
+    // Messages are charged by their serialized size, including the length prefix.
+    let small = emit(1, vec![7]);
+    assert_eq!(small.gas_breakdown.events, 2);
+    let large = emit(1, vec![7; 500]);
+    assert_eq!(large.gas_breakdown.events, 502);
+    assert_eq!(large.gas_used, free.gas_used + 502);
+}
+
+#[test]
+fn test_failed_events_leave_no_effects() {
+    let (vm, store, events, oracle, _) = vm();
+    let state = State::new(store, oracle);
+    vm.pub_mod(store_module());
+    vm.pub_mod(event_module());
+    vm.pub_mod(event_proxy_module());
+
+    let vm = vm.with_event_byte_gas(1_000_000);
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_and_emit_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::OUT_OF_GAS);
+    let tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new("Store").unwrap(),
+        name: Identifier::new("U64").unwrap(),
+        type_params: vec![],
+    };
+    assert!(state.get_resource(&addr("0x1"), &tag).unwrap().is_none());
+    // Only the status event of the failed transaction reaches the handler.
+    assert_eq!(events.data.borrow().len(), 1);
+    let (_, ty_tag, _, _) = events.pop().unwrap();
+    assert!(matches!(ty_tag, TypeTag::Struct(status) if status.module.as_str() == "VMStatus"));
+
+    let event = |layout| {
+        (
+            addr("0x1"),
+            TypeTag::U64,
+            layout,
+            Value::u64(13),
+            None::<ModuleId>,
+        )
+    };
+    let err = vm
+        .apply_events(vec![
+            event(MoveTypeLayout::U64),
+            event(MoveTypeLayout::Bool),
+        ])
+        .unwrap_err();
+    assert_eq!(err.major_status(), StatusCode::VALUE_SERIALIZATION_ERROR);
+    assert!(events.data.borrow().is_empty());
+}
+
+#[test]
+fn test_bank_session_transfer_log() {
+    let (vm, _, _, _, bank) = vm();
+    vm.pub_mod(coins_module());
+    vm.pub_mod(pont_module());
+    vm.pub_mod(signer_module());
+    vm.pub_mod(event_module());
+    vm.pub_mod(pontem_module());
+    vm.pub_mod(account_module());
+
+    let addr_1 = AccountAddress::random();
+    let addr_2 = AccountAddress::random();
+    bank.set_balance(&addr_1, "USDT", 1024);
+    bank.set_balance(&addr_1, "PONT", 64);
+    bank.set_balance(&addr_1, "BTC", 13);
+
+    let (res, log) = vm.execute_script_with_transfer_log(
+        gas(),
+        ExecutionContext::new(100, 100),
+        test_balance_script(addr_1, addr_2, 1024, 64, 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+
+    let log = log
+        .into_iter()
+        .map(|transfer| {
+            (
+                transfer.address,
+                transfer.signer,
+                transfer.ticker.as_str().to_owned(),
+                transfer.kind,
+                transfer.amount,
+            )
+        })
+        .collect::<Vec<_>>();
+    let deposit = |address, signer, ticker: &str, amount| {
+        (
+            address,
+            Some(signer),
+            ticker.to_owned(),
+            TransferKind::Deposit,
+            amount,
+        )
+    };
+    let withdraw = |address, signer, ticker: &str, amount| {
+        (
+            address,
+            Some(signer),
+            ticker.to_owned(),
+            TransferKind::Withdraw,
+            amount,
+        )
+    };
+    assert_eq!(
+        log,
+        vec![
+            deposit(addr_1, 0, "USDT", 512),
+            withdraw(addr_2, 1, "USDT", 512),
+            deposit(addr_1, 0, "PONT", 1),
+            deposit(addr_1, 0, "PONT", 1),
+            deposit(addr_1, 0, "PONT", 1),
+            withdraw(addr_2, 1, "PONT", 2),
+            withdraw(addr_2, 1, "PONT", 1),
+        ]
+    );
+
+    // Nothing is logged for a failed transaction.
+    let (res, log) = vm.execute_script_with_transfer_log(
+        gas(),
+        ExecutionContext::new(100, 100),
+        test_balance_script(addr_1, addr_2, 1024, 64, 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::ABORTED);
+    assert!(log.is_empty());
+}
+
+#[test]
+fn test_modules_under() {
+    let (vm, store, _, oracle, _) = vm();
+    let state = State::new(store, oracle);
+
+    vm.pub_mod(store_module());
+    vm.pub_mod(abort_module());
+    vm.pub_mod(time_module());
+
+    let mut modules = state.modules_under(&CORE_CODE_ADDRESS);
+    modules.sort();
+    let names = modules
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Abort", "Store", "Time"]);
+    assert_eq!(modules[1].1, store_module().code());
+
+    assert!(state.modules_under(&addr("0x2")).is_empty());
+}
@@ -1,10 +1,25 @@
 extern crate alloc;
 mod common;
 
-use crate::common::mock::StorageMock;
+use crate::common::assets::{event_module, gas, store_module, store_u64_script};
+use crate::common::mock::{addr, StorageMock, Utils};
+use crate::common::vm;
+use core::convert::TryFrom;
+use move_core_types::gas_schedule::{
+    CostTable, GasAlgebra, GasConstants, GasCost, GasPrice, GasUnits,
+};
+use move_core_types::identifier::Identifier;
+use move_core_types::language_storage::{ModuleId, TypeTag, CORE_CODE_ADDRESS};
+use move_core_types::vm_status::StatusCode;
+use mvm::data::ExecutionContext;
+use mvm::data::Storage;
 use mvm::gas_schedule::cost_table;
-use mvm::vm_config::loader::{load_vm_config, store_vm_config};
-use mvm::vm_config::VmConfig;
+use mvm::mvm::Mvm;
+use mvm::types::{FunctionTx, Gas, PublishPackageTx, ScriptArg};
+use mvm::vm_config::loader::{legacy_storage_key, load_vm_config, store_vm_config};
+use mvm::vm_config::{VmConfig, DEFAULT_MAX_MODULE_BYTES, DEFAULT_MAX_TYPE_DEPTH};
+use mvm::Vm;
+use parity_scale_codec::Encode;
 
 #[test]
 fn load_store_test() {
@@ -13,6 +28,8 @@ fn load_store_test() {
 
     let vm_config = VmConfig {
         gas_schedule: cost_table,
+        max_module_bytes: 1024,
+        max_type_depth: 16,
     };
     let mock = StorageMock::new();
     store_vm_config(&mock, &vm_config);
@@ -22,8 +39,200 @@ fn load_store_test() {
     assert_eq!(vm_config, loaded_vm_config);
 }
 
+#[test]
+fn load_legacy_config_test() {
+    let mut cost_table = cost_table();
+    cost_table.instruction_table.remove(0);
+
+    // Configs stored before the vm limits were added contain only the gas schedule.
+    let mock = StorageMock::new();
+    mock.insert(&legacy_storage_key(), &cost_table.encode());
+
+    let loaded_vm_config = load_vm_config(&mock).unwrap();
+    assert_eq!(loaded_vm_config.gas_schedule, cost_table);
+    assert_eq!(loaded_vm_config.max_module_bytes, DEFAULT_MAX_MODULE_BYTES);
+    assert_eq!(loaded_vm_config.max_type_depth, DEFAULT_MAX_TYPE_DEPTH);
+
+    // Storing a config replaces the legacy one.
+    let vm_config = VmConfig {
+        max_module_bytes: 1024,
+        ..loaded_vm_config
+    };
+    store_vm_config(&mock, &vm_config);
+    assert!(mock.get(&legacy_storage_key()).is_none());
+    assert_eq!(load_vm_config(&mock).unwrap(), vm_config);
+}
+
 #[test]
 fn load_from_empty_store_test() {
     let loaded_vm_config = load_vm_config(&StorageMock::new()).unwrap();
     assert_eq!(VmConfig::default(), loaded_vm_config);
 }
+
+#[test]
+fn store_cost_table_test() {
+    let (vm, store, event, oracle, bank) = vm();
+
+    let mut cost_table = cost_table();
+    cost_table.instruction_table.remove(0);
+    vm.store_cost_table(&cost_table).unwrap();
+
+    let vm = Mvm::new(store, event, oracle, bank).unwrap();
+    assert_eq!(*vm.cost_table(), cost_table);
+}
+
+#[test]
+fn update_cost_table_test() {
+    let (vm, _, _, _, _) = vm();
+    vm.pub_mod(store_module());
+
+    let run = || {
+        let res = vm.dry_run_script(
+            gas(),
+            ExecutionContext::new(100, 100),
+            store_u64_script(addr("0x1"), 13),
+        );
+        assert_eq!(res.status_code, StatusCode::EXECUTED);
+        res.gas_used
+    };
+
+    let cheap = run();
+
+    let mut cost_table = cost_table();
+    for cost in cost_table.instruction_table.iter_mut() {
+        *cost = GasCost::new(cost.instruction_gas.get() * 10, cost.memory_gas.get());
+    }
+    vm.update_cost_table(cost_table.clone());
+    assert_eq!(*vm.cost_table(), cost_table);
+
+    assert!(run() > cheap);
+}
+
+#[test]
+fn max_module_bytes_test() {
+    let (_, store, event, oracle, bank) = vm();
+    let module = store_module();
+    store_vm_config(
+        &store,
+        &VmConfig {
+            max_module_bytes: module.code().len() as u64 - 1,
+            ..VmConfig::default()
+        },
+    );
+
+    let vm = Mvm::new(store, event, oracle, bank).unwrap();
+    let res = vm.publish_module(gas(), module.clone(), false);
+    assert_eq!(res.status_code, StatusCode::EXCEEDED_MAX_MODULE_SIZE);
+    assert_eq!(res.gas_used, 0);
+
+    // Other publishing paths reject oversized modules before charging or deserializing them.
+    let res = vm.publish_module_bundle_ordered(gas(), vec![event_module(), module.clone()]);
+    assert_eq!(res.status_code, StatusCode::EXCEEDED_MAX_MODULE_SIZE);
+    assert_eq!(res.gas_used, 0);
+    let package = PublishPackageTx::try_from(vec![event_module(), module.clone()]).unwrap();
+    let res = vm.publish_module_package(gas(), package, false);
+    assert_eq!(res.status_code, StatusCode::EXCEEDED_MAX_MODULE_SIZE);
+    assert_eq!(res.gas_used, 0);
+    assert_eq!(
+        vm.validate_module(module.code())
+            .unwrap_err()
+            .major_status(),
+        StatusCode::EXCEEDED_MAX_MODULE_SIZE
+    );
+    assert_eq!(
+        vm.preload_stdlib(&[module.clone()])
+            .unwrap_err()
+            .major_status(),
+        StatusCode::EXCEEDED_MAX_MODULE_SIZE
+    );
+}
+
+#[test]
+fn with_cost_table_test() {
+    let (_, store, event, oracle, bank) = vm();
+    let default_table = cost_table();
+    let table = CostTable {
+        instruction_table: vec![GasCost::new(1, 1); default_table.instruction_table.len()],
+        native_table: vec![GasCost::new(1, 1); default_table.native_table.len()],
+        gas_constants: GasConstants::default(),
+    };
+
+    let vm = Mvm::with_cost_table(store, event, oracle, bank, table.clone());
+    assert_eq!(*vm.cost_table(), table);
+
+    vm.pub_mod(store_module());
+    let res = vm.execute_script(
+        gas(),
+        ExecutionContext::new(100, 100),
+        store_u64_script(addr("0x1"), 13),
+        false,
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+}
+
+#[test]
+fn cost_table_gas_bounds_test() {
+    let (_, store, event, oracle, bank) = vm();
+    let mut table = cost_table();
+    table.gas_constants.maximum_number_of_gas_units = GasUnits::new(1_000);
+    table.gas_constants.min_price_per_gas_unit = GasPrice::new(2);
+    let vm = Mvm::with_cost_table(store, event, oracle, bank, table);
+
+    // `Gas::new` only knows the default bounds, the vm checks its own cost table.
+    let res = vm.publish_module(gas(), event_module(), false);
+    assert_eq!(
+        res.status_code,
+        StatusCode::MAX_GAS_UNITS_EXCEEDS_MAX_GAS_UNITS_BOUND
+    );
+    assert_eq!(res.gas_used, 0);
+    assert_eq!(res.refund, 10_000);
+
+    let res = vm.publish_module(Gas::new(1_000, 1).unwrap(), event_module(), false);
+    assert_eq!(res.status_code, StatusCode::GAS_UNIT_PRICE_BELOW_MIN_BOUND);
+    assert_eq!(res.gas_used, 0);
+
+    let res = vm.publish_module(Gas::new(1_000, 2).unwrap(), event_module(), false);
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+}
+
+#[test]
+fn max_type_depth_test() {
+    let (_, store, event, oracle, bank) = vm();
+    store_vm_config(
+        &store,
+        &VmConfig {
+            max_type_depth: 4,
+            ..VmConfig::default()
+        },
+    );
+    let vm = Mvm::new(store, event, oracle, bank).unwrap();
+    vm.pub_mod(event_module());
+
+    let emit = |type_arg, arg| {
+        vm.execute_function(
+            gas(),
+            ExecutionContext::new(100, 100),
+            FunctionTx::new(
+                ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("Event").unwrap()),
+                Identifier::new("emit").unwrap(),
+                vec![arg],
+                vec![type_arg],
+                vec![addr("0x1")],
+            ),
+            false,
+        )
+    };
+
+    let res = emit(
+        TypeTag::Vector(Box::new(TypeTag::U8)),
+        ScriptArg::VectorU8(vec![1]),
+    );
+    assert_eq!(res.status_code, StatusCode::EXECUTED);
+
+    let mut deep = TypeTag::U8;
+    for _ in 0..5 {
+        deep = TypeTag::Vector(Box::new(deep));
+    }
+    let res = emit(deep, ScriptArg::U8(1));
+    assert_eq!(res.status_code, StatusCode::VM_MAX_TYPE_DEPTH_REACHED);
+}